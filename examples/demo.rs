@@ -1,30 +1,32 @@
-mod commons;
-mod render;
-
 //use sdl2::event::Event;
 //use sdl2::keyboard::Keycode;
 
-use crate::commons::{Bounds, Pos};
-use crate::render::backend::raqote::RaqoteBackend;
-use crate::render::{BackgroundImage, Border, BorderSide, BorderStyle, Child, Color, Outline, OutlineShadow, OutlineStyle, Renderer};
+use new_renderer::{
+    BackgroundBox, BackgroundImage, BackgroundPosition, BackgroundRepeat, BackgroundSize, Border, BorderSide, BorderStyle, Bounds, Child, Color, Outline,
+    OutlineShadow, OutlineStyle, Pos, RaqoteBackend, Renderer,
+};
 
-fn main() {
-    let mut r = Renderer::new(RaqoteBackend::new("out.png".to_string(), 800, 600));
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut r = Renderer::new(RaqoteBackend::new(800, 600));
+    r.set_viewport(Some(Bounds {
+        a: Pos::ZERO,
+        b: Pos { x: 800., y: 600. },
+    }));
 
-    let image = r.create_image(64, 64, gen_checkerboard(64, 64, 16));
+    let image = r.create_image(64, 64, gen_checkerboard(64, 64, 16))?;
 
     let parent = r.create_container(0);
     let child1 = r.create_container(1);
     let child2 = r.create_container(2);
     let text = r.create_text(3);
 
-    r.set_text_data(text, "Hello".to_string());
+    r.set_text_data(text, "Hello".to_string())?;
 
-    r.insert_child(parent, 0, Child::Container(child1));
-    r.insert_child(parent, 1, Child::Container(child2));
-    r.insert_child(parent, 2, Child::Text(text));
+    r.insert_child(parent, 0, Child::Container(child1))?;
+    r.insert_child(parent, 1, Child::Container(child2))?;
+    r.insert_child(parent, 2, Child::Text(text))?;
 
-    r.set_background_color(parent, Color::RED);
+    r.set_background_color(parent, Color::RED)?;
     r.set_border(
         parent,
         Some(Border {
@@ -37,17 +39,18 @@ fn main() {
             }),
             left: None,
         }),
-    );
+    )?;
 
-    r.set_background_color(child1, Color::GREEN);
+    r.set_background_color(child1, Color::GREEN)?;
     r.set_outline(
         child1,
         Some(Outline {
             width: 1.,
+            offset: 0.,
             style: OutlineStyle::Solid,
             color: Color::BLUE,
         }),
-    );
+    )?;
     r.set_outline_shadows(
         child1,
         vec![OutlineShadow {
@@ -56,9 +59,19 @@ fn main() {
             spread: 5.,
             color: Color { r: 127, g: 127, b: 127, a: 127 },
         }],
-    );
-
-    r.set_background_images(child2, vec![BackgroundImage::Image { image }]);
+    )?;
+
+    r.set_background_images(
+        child2,
+        vec![BackgroundImage::Image {
+            image,
+            size: BackgroundSize::Explicit(Some(32.), None),
+            position: BackgroundPosition::CENTER,
+            repeat: (BackgroundRepeat::Repeat, BackgroundRepeat::Repeat),
+            origin: BackgroundBox::PaddingBox,
+            clip: BackgroundBox::BorderBox,
+        }],
+    )?;
 
     r.render_container(
         parent,
@@ -77,7 +90,9 @@ fn main() {
             },
             Bounds::ZERO,
         ],
-    );
+    )?;
+
+    r.backend().save_png("out.png")?;
 
     /*
         let sdl = sdl2::init().unwrap();
@@ -99,6 +114,8 @@ fn main() {
             }
         }
     */
+
+    Ok(())
 }
 
 // square_size has to be power of 2
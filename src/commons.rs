@@ -6,7 +6,7 @@ use std::fmt::{self, Debug, Formatter};
 pub type Au = f32;
 
 /// 2D Point
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Pos {
     pub x: Au,
     pub y: Au,
@@ -33,7 +33,7 @@ impl Debug for Pos {
 }
 
 /// Bounding box defined by two points
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Bounds {
     pub a: Pos,
     pub b: Pos,
@@ -86,6 +86,45 @@ impl Bounds {
     pub fn contains(&self, pos: Pos) -> bool {
         pos.x > self.a.x && pos.x < self.b.x && pos.y > self.a.y && pos.y < self.b.y
     }
+
+    #[inline]
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.a.x < other.b.x && other.a.x < self.b.x && self.a.y < other.b.y && other.a.y < self.b.y
+    }
+
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            a: Pos {
+                x: self.a.x.min(other.a.x),
+                y: self.a.y.min(other.a.y),
+            },
+            b: Pos {
+                x: self.b.x.max(other.b.x),
+                y: self.b.y.max(other.b.y),
+            },
+        }
+    }
+
+    // might be empty
+    #[inline]
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self {
+            a: Pos {
+                x: self.a.x.max(other.a.x),
+                y: self.a.y.max(other.a.y),
+            },
+            b: Pos {
+                x: self.b.x.min(other.b.x),
+                y: self.b.y.min(other.b.y),
+            },
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.b.x <= self.a.x || self.b.y <= self.a.y
+    }
 }
 
 impl Debug for Bounds {
@@ -0,0 +1,24 @@
+// x renderer is usable as a library (demo is in examples/)
+// x public API is re-exported here, modules are private
+//   (so internals can change without breaking anyone)
+// x rendering backends are optional (see features in Cargo.toml)
+
+mod commons;
+mod render;
+
+pub use crate::commons::{Au, Bounds, Pos};
+
+// renderer & handles
+pub use crate::render::{ContainerId, DecodingError, ImageId, RenderError, RenderStats, Renderer, TextId};
+
+// value types
+pub use crate::render::{
+    BackgroundBox, BackgroundImage, BackgroundPosition, BackgroundRepeat, BackgroundSize, BlendMode, Border, BorderRadius, BorderSide, BorderStyle, Child, ClipPath,
+    Color, Filter, ImageRendering, InsetShadow, Mask, NineSliceRepeat, Outline, OutlineShadow, OutlineStyle, Overflow, PathCommand, SideOffsets,
+};
+
+// for backend implementations
+pub use crate::render::backend::{FillStyle, LayerBuilder, RenderBackend};
+
+#[cfg(feature = "raqote")]
+pub use crate::render::backend::raqote::RaqoteBackend;
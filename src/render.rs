@@ -8,6 +8,7 @@
 
 use crate::commons::{Bounds, Pos};
 use std::ops::Index;
+use std::time::Duration;
 
 // handles
 // public but opaque types
@@ -25,9 +26,30 @@ pub struct TextId(usize);
 mod value_types;
 pub use self::value_types::*;
 
+// decoding
+mod decoding;
+pub use self::decoding::DecodingError;
+
+// errors
+mod error;
+pub use self::error::RenderError;
+
+mod animation;
+use self::animation::Animation;
+
+mod background;
+mod border;
+mod caching;
+mod damage;
+mod masking;
+mod ops;
+mod stacking;
+
 // and backend
 pub mod backend;
 use self::backend::{FillStyle, LayerBuilder, RenderBackend};
+use self::ops::Op;
+use self::stacking::StackedChild;
 
 // where:
 // - `RB` is `RenderBackend` implementation
@@ -47,6 +69,15 @@ impl<RB: RenderBackend, BK: Copy> Renderer<RB, BK> {
         }
     }
 
+    // to get the results (pixels, ...)
+    pub fn backend(&self) -> &RB {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut RB {
+        &mut self.backend
+    }
+
     // container
     pub fn create_container(&mut self, bounds_key: BK) -> ContainerId {
         // TODO: maybe defaults shouldn't be here
@@ -63,83 +94,366 @@ impl<RB: RenderBackend, BK: Copy> Renderer<RB, BK> {
         self.ui_state.inset_shadows.push(Vec::new());
         self.ui_state.colors.push(Color::BLACK);
         self.ui_state.borders.push(None);
+        self.ui_state.filters.push(Vec::new());
+        self.ui_state.backdrop_filters.push(Vec::new());
+        self.ui_state.backdrop_textures.push(None);
+        self.ui_state.blend_modes.push(BlendMode::Normal);
+        self.ui_state.clip_paths.push(None);
+        self.ui_state.masks.push(None);
+        self.ui_state.z_indices.push(None);
+
+        self.ui_state.dirty.push(true);
+        self.ui_state.last_bounds.push(Bounds::ZERO);
+        self.ui_state.stacked.push(Vec::new());
+        self.ui_state.layers.push(None);
+        self.ui_state.layers_valid.push(false);
+        self.ui_state.last_visual_bounds.push(Bounds::ZERO);
+        self.ui_state.last_culls.push(Bounds::ZERO);
+        self.ui_state.flow_extents.push(Bounds::ZERO);
 
         ContainerId(self.ui_state.background_colors.len() - 1)
     }
 
-    pub fn insert_child(&mut self, container: ContainerId, index: usize, child: Child) {
+    pub fn insert_child(&mut self, container: ContainerId, index: usize, child: Child) -> Result<(), RenderError> {
+        self.ui_state.check_container(container)?;
+        self.ui_state.check_child(child)?;
+        self.ui_state.check_cycle(container, child)?;
+
+        let len = self.ui_state.children[container.0].len();
+
+        if index > len {
+            return Err(RenderError::IndexOutOfRange { index, len });
+        }
+
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.children[container.0].insert(index, child);
+
+        Ok(())
     }
 
-    pub fn remove_child(&mut self, container: ContainerId, child: Child) {
+    pub fn remove_child(&mut self, container: ContainerId, child: Child) -> Result<(), RenderError> {
+        self.ui_state.check_child(child)?;
+        self.ui_state.mark_dirty(container)?;
+
+        match child {
+            Child::Container(ch) => self.ui_state.damage_subtree(ch),
+            Child::Text(text) => self.ui_state.pending_damage.push(self.ui_state.text_last_visual_bounds[text.0]),
+        }
+
         self.ui_state.children[container.0].retain(|ch| *ch != child);
+
+        Ok(())
+    }
+
+    // drop ops which are hidden behind opaque rects (see ops.rs)
+    pub fn set_occlusion_culling(&mut self, value: bool) {
+        self.ui_state.occlusion_culling = value;
+    }
+
+    // usually the size of the render target, `None` means no culling
+    pub fn set_viewport(&mut self, value: Option<Bounds>) {
+        self.ui_state.viewport = value;
     }
 
     // setters (in order in which they are needed during rendering)
 
     // TODO: transform
 
-    pub fn set_overflow(&mut self, container: ContainerId, value: Overflow) {
+    // `None` is auto, anything else creates a new stacking context
+    pub fn set_z_index(&mut self, container: ContainerId, value: Option<i32>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
+        self.ui_state.z_indices[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_overflow(&mut self, container: ContainerId, value: Overflow) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.overflows[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_opacity(&mut self, container: ContainerId, value: f32) {
+    pub fn set_opacity(&mut self, container: ContainerId, value: f32) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.opacities[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_border_radius(&mut self, container: ContainerId, value: Option<BorderRadius>) {
+    pub fn set_border_radius(&mut self, container: ContainerId, value: Option<BorderRadius>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.border_radii[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_outline_shadows(&mut self, container: ContainerId, value: Vec<OutlineShadow>) {
+    pub fn set_outline_shadows(&mut self, container: ContainerId, value: Vec<OutlineShadow>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.outline_shadows[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_outline(&mut self, container: ContainerId, value: Option<Outline>) {
+    pub fn set_outline(&mut self, container: ContainerId, value: Option<Outline>) -> Result<(), RenderError> {
+        self.ui_state.check_container(container)?;
+
+        if let Some(Outline { style: OutlineStyle::Dotted, .. }) = value {
+            self.ensure_circle_texture()?;
+        }
+
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.outlines[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_background_color(&mut self, container: ContainerId, value: Color) {
+    pub fn set_background_color(&mut self, container: ContainerId, value: Color) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.background_colors[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_background_images(&mut self, container: ContainerId, value: Vec<BackgroundImage>) {
+    pub fn set_background_images(&mut self, container: ContainerId, value: Vec<BackgroundImage>) -> Result<(), RenderError> {
+        self.ui_state.check_background_images(&value)?;
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.background_images[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_inset_shadows(&mut self, container: ContainerId, value: Vec<InsetShadow>) {
+    pub fn set_inset_shadows(&mut self, container: ContainerId, value: Vec<InsetShadow>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.inset_shadows[container.0] = value;
+
+        Ok(())
     }
 
     // TODO: set_text_shadow
 
-    pub fn set_color(&mut self, container: ContainerId, value: Color) {
+    pub fn set_color(&mut self, container: ContainerId, value: Color) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.colors[container.0] = value;
+
+        Ok(())
     }
 
-    pub fn set_border(&mut self, container: ContainerId, value: Option<Border>) {
+    pub fn set_border(&mut self, container: ContainerId, value: Option<Border>) -> Result<(), RenderError> {
+        self.ui_state.check_container(container)?;
+
+        if let Some(b) = &value {
+            let sides = [b.top, b.right, b.bottom, b.left];
+
+            if sides.iter().flatten().any(|s| s.style == BorderStyle::Dotted) {
+                self.ensure_circle_texture()?;
+            }
+        }
+
+        self.ui_state.mark_dirty(container)?;
         self.ui_state.borders[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_filters(&mut self, container: ContainerId, value: Vec<Filter>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
+        self.ui_state.filters[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_backdrop_filters(&mut self, container: ContainerId, value: Vec<Filter>) -> Result<(), RenderError> {
+        self.ui_state.check_container(container)?;
+
+        // what's behind is copied there (during rendering), filtered & drawn back
+        let texture = &mut self.ui_state.backdrop_textures[container.0];
+
+        match *texture {
+            None if !value.is_empty() => *texture = Some(self.backend.create_snapshot_texture()?),
+            Some(prev) if value.is_empty() => {
+                self.backend.delete_texture(prev);
+                *texture = None;
+            }
+            _ => {}
+        }
+
+        self.ui_state.mark_dirty(container)?;
+        self.ui_state.backdrop_filters[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_blend_mode(&mut self, container: ContainerId, value: BlendMode) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
+        self.ui_state.blend_modes[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_clip_path(&mut self, container: ContainerId, value: Option<ClipPath>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
+        self.ui_state.clip_paths[container.0] = value;
+
+        Ok(())
+    }
+
+    pub fn set_mask(&mut self, container: ContainerId, value: Option<Mask>) -> Result<(), RenderError> {
+        self.ui_state.mark_dirty(container)?;
+
+        let size = masking::MASK_SIZE;
+        let mask = match value {
+            None => None,
+            Some(Mask::Image(image)) => {
+                self.ui_state.check_image(image)?;
+                Some(MaskTexture::Image(image))
+            }
+            Some(Mask::LinearGradient { angle, stops }) => {
+                let data = masking::gen_gradient_mask(&stops, masking::linear(angle));
+                Some(MaskTexture::Generated(self.backend.create_texture(size, size, data)?))
+            }
+            Some(Mask::RadialGradient { stops }) => {
+                let data = masking::gen_gradient_mask(&stops, masking::radial);
+                Some(MaskTexture::Generated(self.backend.create_texture(size, size, data)?))
+            }
+        };
+
+        // (only after the new one was created successfully)
+        if let Some(MaskTexture::Generated(texture)) = std::mem::replace(&mut self.ui_state.masks[container.0], mask) {
+            self.backend.delete_texture(texture);
+        }
+
+        Ok(())
     }
 
     // image
-    pub fn create_image(&mut self, width: i32, height: i32, data: Box<[u8]>) -> ImageId {
+    pub fn create_image(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Result<ImageId, RenderError> {
         // TODO: put it to some existing/new texture (rect-packing)
-        self.ui_state.textures.push(self.backend.create_texture(width, height, data));
+        self.ui_state.textures.push(self.backend.create_texture(width, height, data)?);
+        self.ui_state.image_sizes.push((width, height));
+        self.ui_state.image_renderings.push(ImageRendering::Smooth);
+
+        Ok(ImageId(self.ui_state.textures.len() - 1))
+    }
+
+    pub fn set_image_rendering(&mut self, image: ImageId, value: ImageRendering) -> Result<(), RenderError> {
+        self.ui_state.check_image(image)?;
+        self.ui_state.image_renderings[image.0] = value;
+        self.ui_state.mark_image_users_dirty(image);
+
+        Ok(())
+    }
+
+    // png, jpeg, gif (first frame), webp
+    pub fn create_image_from_encoded(&mut self, bytes: &[u8]) -> Result<ImageId, RenderError> {
+        let decoding::DecodedImage { width, height, data } = decoding::decode_image(bytes)?;
+
+        self.create_image(width, height, data)
+    }
+
+    // gif, apng, webp (but any other format works too)
+    // first frame is shown until `tick()` is called
+    pub fn create_animated_image(&mut self, bytes: &[u8]) -> Result<ImageId, RenderError> {
+        let animation = Animation::new(decoding::decode_animation(bytes)?);
+        let (width, height) = animation.size();
+        let image = self.create_image(width, height, animation.canvas().into())?;
+
+        self.ui_state.animations.push((image, animation));
+
+        Ok(image)
+    }
 
-        ImageId(self.ui_state.textures.len() - 1)
+    // advance animated images, `now` is time since any (but fixed) point
+    // returns true if anything has changed (and it's worth to render again)
+    pub fn tick(&mut self, now: Duration) -> bool {
+        let UiState { animations, textures, .. } = &mut self.ui_state;
+
+        let mut advanced = Vec::new();
+
+        for (image, animation) in animations {
+            if animation.advance(now) {
+                self.backend.update_texture(textures[image.0], |pixels| pixels.copy_from_slice(animation.canvas()));
+                advanced.push(*image);
+            }
+        }
+
+        for image in &advanced {
+            self.ui_state.damage_image_users(*image);
+        }
+
+        !advanced.is_empty()
+    }
+
+    // replace all pixels, the image can be resized
+    // (handle stays the same so containers referencing it are still valid)
+    pub fn set_image_data(&mut self, image: ImageId, width: i32, height: i32, data: Box<[u8]>) -> Result<(), RenderError> {
+        self.ui_state.check_image(image)?;
+        error::check_image_data(width, height, &data)?;
+
+        // stop animation (if any)
+        self.ui_state.animations.retain(|(img, _)| *img != image);
+
+        if self.ui_state.image_sizes[image.0] == (width, height) {
+            self.backend.update_texture(self.ui_state.textures[image.0], |pixels| pixels.copy_from_slice(&data));
+            self.ui_state.damage_image_users(image);
+        } else {
+            // TODO: atlas (once there is one)
+            let prev = self.ui_state.textures[image.0];
+
+            self.ui_state.textures[image.0] = self.backend.create_texture(width, height, data)?;
+            self.ui_state.image_sizes[image.0] = (width, height);
+            self.backend.delete_texture(prev);
+            self.ui_state.mark_image_users_dirty(image);
+        }
+
+        Ok(())
     }
 
-    pub fn set_image_data(&mut self /* data: rgb &[u8] */) {}
+    // update only part of the image (video frames, canvas, ...)
+    // `data` are RGBA pixels of the `width * height` rect at `x, y`
+    pub fn set_image_sub_data(&mut self, image: ImageId, x: i32, y: i32, width: i32, height: i32, data: &[u8]) -> Result<(), RenderError> {
+        self.ui_state.check_image(image)?;
+        error::check_image_data(width, height, data)?;
+
+        let (image_width, image_height) = self.ui_state.image_sizes[image.0];
+
+        // i64 so that it can't overflow
+        if x < 0 || y < 0 || x as i64 + width as i64 > image_width as i64 || y as i64 + height as i64 > image_height as i64 {
+            return Err(RenderError::RectOutOfImage);
+        }
+
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        self.backend.update_texture(self.ui_state.textures[image.0], |pixels| {
+            let row_len = width as usize * 4;
+
+            for (row, src) in data.chunks_exact(row_len).enumerate() {
+                let start = (((y as usize + row) * image_width as usize) + x as usize) * 4;
+                pixels[start..start + row_len].copy_from_slice(src);
+            }
+        });
+
+        self.ui_state.damage_image_users(image);
+
+        Ok(())
+    }
 
     // text
     pub fn create_text(&mut self, bounds_key: BK) -> TextId {
         self.ui_state.text_bounds_keys.push(bounds_key);
         self.ui_state.text_layers.push(self.backend.create_layer());
+        self.ui_state.text_last_bounds.push(Bounds::ZERO);
+        self.ui_state.text_last_visual_bounds.push(Bounds::ZERO);
 
         TextId(self.ui_state.text_layers.len() - 1)
     }
 
-    pub fn set_text_data(&mut self, text: TextId, str: String /* TODO: texture + glyphs */) {
+    pub fn set_text_data(&mut self, text: TextId, str: String /* TODO: texture + glyphs */) -> Result<(), RenderError> {
+        self.ui_state.check_text(text)?;
+        self.ui_state.pending_damage.push(self.ui_state.text_last_visual_bounds[text.0]);
+
         self.backend.rebuild_layer_with(self.ui_state.text_layers[text.0], |b| {
             let mut x = 0.;
 
@@ -155,26 +469,99 @@ impl<RB: RenderBackend, BK: Copy> Renderer<RB, BK> {
                 x += 15.;
             }
         });
+
+        Ok(())
     }
 
-    pub fn render_container(&mut self, container: ContainerId, bounds: &impl Index<BK, Output = Bounds>) {
-        let layer = self.ui_state.root_layer;
+    // only the layers which have changed are rebuilt (see caching.rs)
+    pub fn render_container(&mut self, container: ContainerId, bounds: &impl Index<BK, Output = Bounds>) -> Result<(), RenderError> {
+        self.ui_state.check_container(container)?;
+
+        let root_layer = self.ui_state.root_layer;
         let current_bounds = bounds[self.ui_state.bounds_keys[container.0]];
 
         let Self { backend, ui_state, .. } = self;
 
-        self.backend.rebuild_layer_with(layer, |builder| {
+        let mut stats = RenderStats::default();
+
+        for (ct, current_bounds, cull) in ui_state.update_layers(backend, container, current_bounds, bounds) {
+            let layer = match ui_state.layers[ct.0] {
+                Some(layer) if ct != container => layer,
+                _ => root_layer,
+            };
+
+            let mut ops = Vec::new();
             let mut ctx = RenderContext {
-                builder,
+                builder: &mut ops,
                 ui_state,
                 bounds,
                 current_bounds,
+                cull,
+                stats: RenderStats::default(),
             };
 
-            ctx.render_container(container);
-        });
+            let res = ctx.render_container(ct);
+
+            stats.rendered += ctx.stats.rendered;
+            stats.culled += ctx.stats.culled;
+
+            // (rebuilt the next time)
+            if let Err(e) = res {
+                ui_state.dirty[ct.0] = true;
+                return Err(e);
+            }
+
+            if ui_state.occlusion_culling {
+                stats.occluded += ops::cull_occluded(&mut ops);
+            }
+
+            let (batches, merged) = ops::batch_rects(&mut ops);
+            stats.batches += batches;
+            stats.merged += merged;
+
+            backend.rebuild_layer_with(layer, |builder| ops::replay(std::mem::take(&mut ops), builder));
+        }
+
+        self.ui_state.stats = stats;
+
+        let damage = self.ui_state.take_damage();
+
+        // (kept for the next time)
+        if let Err(e) = self.backend.render_layer(root_layer, &damage) {
+            self.ui_state.pending_damage = damage;
+            return Err(e);
+        }
+
+        self.ui_state.last_damage = damage;
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.ui_state.stats
+    }
+
+    // what has changed (and was redrawn) during the last `render_container()`
+    pub fn damage(&self) -> &[Bounds] {
+        &self.ui_state.last_damage
+    }
+
+    // topmost container at the pos (in the same order as it's painted)
+    pub fn hit_test(&self, container: ContainerId, bounds: &impl Index<BK, Output = Bounds>, pos: Pos) -> Result<Option<ContainerId>, RenderError> {
+        self.ui_state.check_container(container)?;
+
+        let current_bounds = bounds[self.ui_state.bounds_keys[container.0]];
+
+        Ok(self.ui_state.hit_test(container, current_bounds, bounds, pos))
+    }
+
+    fn ensure_circle_texture(&mut self) -> Result<(), RenderError> {
+        if self.ui_state.circle_texture.is_none() {
+            let size = border::CIRCLE_SIZE;
+            self.ui_state.circle_texture = Some(self.backend.create_texture(size, size, border::gen_circle())?);
+        }
 
-        self.backend.render_layer(layer);
+        Ok(())
     }
 }
 
@@ -195,12 +582,49 @@ struct UiState<RB: RenderBackend, BK> {
     inset_shadows: Vec<Vec<InsetShadow>>,
     colors: Vec<Color>,
     borders: Vec<Option<Border>>,
+    filters: Vec<Vec<Filter>>,
+    backdrop_filters: Vec<Vec<Filter>>,
+    // (only while there are some backdrop-filters)
+    backdrop_textures: Vec<Option<RB::TextureId>>,
+    blend_modes: Vec<BlendMode>,
+    clip_paths: Vec<Option<ClipPath>>,
+    masks: Vec<Option<MaskTexture<RB>>>,
+    z_indices: Vec<Option<i32>>,
+
+    // see caching.rs
+    dirty: Vec<bool>,
+    last_bounds: Vec<Bounds>,
+    stacked: Vec<Vec<StackedChild>>,
+    layers: Vec<Option<RB::LayerId>>,
+    layers_valid: Vec<bool>,
+    root: Option<ContainerId>,
+
+    // see damage.rs
+    last_visual_bounds: Vec<Bounds>,
+    pending_damage: Vec<Bounds>,
+    last_damage: Vec<Bounds>,
+    extent: Bounds,
+    backdrops: Vec<(Bounds, Bounds)>,
+
+    // culling
+    viewport: Option<Bounds>,
+    last_culls: Vec<Bounds>,
+    flow_extents: Vec<Bounds>,
+    stats: RenderStats,
+    occlusion_culling: bool,
 
     text_bounds_keys: Vec<BK>,
     text_layers: Vec<RB::LayerId>,
+    text_last_bounds: Vec<Bounds>,
+    text_last_visual_bounds: Vec<Bounds>,
 
     root_layer: RB::LayerId,
     textures: Vec<RB::TextureId>,
+    image_sizes: Vec<(i32, i32)>,
+    image_renderings: Vec<ImageRendering>,
+    animations: Vec<(ImageId, Animation)>,
+    // msdf circle for dots, created on first use
+    circle_texture: Option<RB::TextureId>,
 }
 
 impl<RB: RenderBackend, BK: Copy> UiState<RB, BK> {
@@ -218,36 +642,167 @@ impl<RB: RenderBackend, BK: Copy> UiState<RB, BK> {
             inset_shadows: Vec::new(),
             colors: Vec::new(),
             borders: Vec::new(),
+            filters: Vec::new(),
+            backdrop_filters: Vec::new(),
+            backdrop_textures: Vec::new(),
+            blend_modes: Vec::new(),
+            clip_paths: Vec::new(),
+            masks: Vec::new(),
+            z_indices: Vec::new(),
+
+            dirty: Vec::new(),
+            last_bounds: Vec::new(),
+            stacked: Vec::new(),
+            layers: Vec::new(),
+            layers_valid: Vec::new(),
+            root: None,
+
+            last_visual_bounds: Vec::new(),
+            pending_damage: Vec::new(),
+            last_damage: Vec::new(),
+            extent: Bounds::ZERO,
+            backdrops: Vec::new(),
+
+            viewport: None,
+            last_culls: Vec::new(),
+            flow_extents: Vec::new(),
+            stats: RenderStats::default(),
+            occlusion_culling: false,
 
             text_bounds_keys: Vec::new(),
             text_layers: Vec::new(),
+            text_last_bounds: Vec::new(),
+            text_last_visual_bounds: Vec::new(),
 
             root_layer,
             textures: Vec::new(),
+            image_sizes: Vec::new(),
+            image_renderings: Vec::new(),
+            animations: Vec::new(),
+            circle_texture: None,
         }
     }
 }
 
+// image (whatever texture it has now) or our own texture
+enum MaskTexture<RB: RenderBackend> {
+    Image(ImageId),
+    Generated(RB::TextureId),
+}
+
 struct RenderContext<'a, RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> {
-    builder: &'a mut RB::LayerBuilder,
+    builder: &'a mut Vec<Op<RB>>,
     ui_state: &'a UiState<RB, BK>,
     bounds: &'a BS,
     current_bounds: Bounds,
+    // in the same coords as the bounds
+    cull: Bounds,
+    stats: RenderStats,
 }
 
 impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<'_, RB, BK, BS> {
-    fn render_container(&mut self, container: ContainerId) {
+    // stacking context (children contexts are only referenced)
+    fn render_container(&mut self, container: ContainerId) -> Result<(), RenderError> {
         // TODO: transform
         // TODO: overflow (scroll)
-        // TODO: opacity
         // TODO: border_radius (clip downwards, (border/shadow only on this level))
 
+        // backdrop-filters always create a context, and they need to be applied
+        // before any group is opened (otherwise they'd see an empty offscreen target)
+        if let Some(texture) = self.ui_state.backdrop_textures[container.0] {
+            self.render_backdrop(container, texture);
+        }
+
+        // whole subtree is rendered offscreen first
+        let blend_mode = self.ui_state.blend_modes[container.0];
+        let mut filters = self.ui_state.filters[container.0].clone();
+
+        // same as if it was the last filter
+        if self.ui_state.opacities[container.0] < 1. {
+            filters.push(Filter::Opacity(self.ui_state.opacities[container.0]));
+        }
+
+        if blend_mode != BlendMode::Normal {
+            self.builder.push_blend_mode(blend_mode);
+        }
+
+        let mask = self.ui_state.masks[container.0].as_ref().map(|m| match *m {
+            MaskTexture::Image(image) => self.ui_state.textures[image.0],
+            MaskTexture::Generated(texture) => texture,
+        });
+
+        if let Some(texture) = mask {
+            self.builder.push_mask(self.current_bounds, texture);
+        }
+
+        let clip_path = &self.ui_state.clip_paths[container.0];
+
+        if let Some(clip) = clip_path {
+            self.builder.push_clip_path(&masking::clip_path(clip, self.current_bounds));
+        }
+
+        if !filters.is_empty() {
+            self.builder.push_filters(&filters);
+        }
+
+        let prev_cull = self.cull;
+        self.cull = self.ui_state.narrow_cull(container, self.current_bounds, self.cull);
+
+        let (negative, rest) = self.ui_state.stacked_children(container, self.current_bounds, self.bounds);
+
+        self.render_box(container, &negative)?;
+        self.render_stacked_children(&rest);
+
+        self.cull = prev_cull;
+
+        if !filters.is_empty() {
+            self.builder.pop_filters();
+        }
+
+        if clip_path.is_some() {
+            self.builder.pop_clip_path();
+        }
+
+        if mask.is_some() {
+            self.builder.pop_mask();
+        }
+
+        if blend_mode != BlendMode::Normal {
+            self.builder.pop_blend_mode();
+        }
+
+        Ok(())
+    }
+
+    // what's behind (and around, for blur) is snapshotted & replaced with its filtered copy
+    // (clipped to the border box)
+    fn render_backdrop(&mut self, container: ContainerId, texture: RB::TextureId) {
+        let filters = &self.ui_state.backdrop_filters[container.0];
+        let sample = self.current_bounds.inflate_uniform(damage::filters_reach(filters));
+        let radii = match &self.ui_state.border_radii[container.0] {
+            Some(r) => border::fit_radii(self.current_bounds, r),
+            None => [0.; 4],
+        };
+        let uv = Bounds { a: Pos::ZERO, b: Pos::ONE };
+
+        self.builder.push_clip_path(&masking::rounded_rect(self.current_bounds, radii));
+        self.builder.push_snapshot(sample, texture);
+        self.builder.push_filters(filters);
+        self.builder.push_rect(sample, FillStyle::Texture(texture, uv, ImageRendering::Pixelated));
+        self.builder.pop_filters();
+        self.builder.pop_clip_path();
+    }
+
+    // the container itself & the non-context part of its subtree
+    fn render_box(&mut self, container: ContainerId, negative: &[StackedChild]) -> Result<(), RenderError> {
+        self.stats.rendered += 1;
+
         for s in &self.ui_state.outline_shadows[container.0] {
             self.render_outline_shadow(s);
         }
 
         if let Some(o) = &self.ui_state.outlines[container.0] {
-            self.render_outline(o);
+            self.render_outline(o, self.ui_state.border_radii[container.0].as_ref())?;
         }
 
         // TODO: clip if Overflow::Hidden
@@ -256,20 +811,25 @@ impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<
         self.render_background_color(self.ui_state.background_colors[container.0]);
 
         for b in &self.ui_state.background_images[container.0] {
-            self.render_background_image(b);
+            self.render_background_image(b, self.ui_state.borders[container.0].as_ref());
         }
 
         for s in &self.ui_state.inset_shadows[container.0] {
             self.render_inset_shadow(s);
         }
 
+        self.render_stacked_children(negative);
+
         for ch in &self.ui_state.children[container.0] {
             let prev_bounds = self.current_bounds;
 
             match ch {
+                // painted later, as part of the stacking context
+                Child::Container(child_ct) if self.ui_state.creates_stacking_context(*child_ct) => {}
+                Child::Container(child_ct) if !self.ui_state.flow_extents[child_ct.0].intersects(&self.cull) => self.stats.culled += 1,
                 Child::Container(child_ct) => {
                     self.current_bounds = self.bounds[self.ui_state.bounds_keys[child_ct.0]].translate(prev_bounds.a);
-                    self.render_container(*child_ct);
+                    self.render_box(*child_ct, &[])?;
                 }
                 Child::Text(child_text) => self.render_text(*child_text),
             }
@@ -278,7 +838,23 @@ impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<
         }
 
         if let Some(b) = &self.ui_state.borders[container.0] {
-            self.render_border(b);
+            self.render_border(b, self.ui_state.border_radii[container.0].as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    // each one has its own layer
+    fn render_stacked_children(&mut self, children: &[StackedChild]) {
+        for ch in children {
+            if !self.ui_state.is_visible(ch, self.cull) {
+                self.stats.culled += 1;
+                continue;
+            }
+
+            if let Some(layer) = self.ui_state.layers[ch.container.0] {
+                self.builder.push_layer(layer, ch.bounds.a);
+            }
         }
     }
 
@@ -291,64 +867,86 @@ impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<
             .push_rect(self.current_bounds.inflate_uniform(shadow.spread), FillStyle::SolidColor(shadow.color));
     }
 
-    fn render_outline(&mut self, outline: &Outline) {
-        let Outline { width, color, .. } = *outline;
-        let Bounds { a, b } = self.current_bounds;
-
-        // top
-        self.builder.push_rect(
-            Bounds {
-                a,
-                b: Pos { x: b.x + width, y: a.y - width },
-            },
-            FillStyle::SolidColor(color),
-        );
-
-        // right
-        self.builder.push_rect(
-            Bounds {
-                a: Pos { x: b.x + width, y: a.y },
-                b: Pos { x: b.x, y: b.y + width },
-            },
-            FillStyle::SolidColor(color),
-        );
-
-        // bottom
-        self.builder.push_rect(
-            Bounds {
-                a: Pos { x: a.x - width, y: b.y + width },
-                b,
-            },
-            FillStyle::SolidColor(color),
-        );
-
-        // left
-        self.builder.push_rect(
-            Bounds {
-                a: Pos { x: a.x - width, y: a.y - width },
-                b: Pos { x: a.x, y: b.y },
-            },
-            FillStyle::SolidColor(color),
-        );
-    }
-
     fn render_background_color(&mut self, color: Color) {
         if color.a != 0 {
             self.builder.push_rect(self.current_bounds, FillStyle::SolidColor(color));
         }
     }
 
-    fn render_background_image(&mut self, background_image: &BackgroundImage) {
+    fn render_background_image(&mut self, background_image: &BackgroundImage, border: Option<&Border>) {
         match background_image {
-            BackgroundImage::Image { image } => self.builder.push_rect(
-                self.current_bounds,
-                FillStyle::Texture(self.ui_state.textures[image.0], Bounds { a: Pos::ZERO, b: Pos::ONE }),
-            ),
+            BackgroundImage::Image {
+                image,
+                size,
+                position,
+                repeat,
+                origin,
+                clip,
+            } => {
+                let (width, height) = self.ui_state.image_sizes[image.0];
+                let rects = background::image_rects(
+                    (width as f32, height as f32),
+                    *size,
+                    *position,
+                    *repeat,
+                    self.background_box(*origin, border),
+                    self.background_box(*clip, border),
+                );
+
+                for (bounds, uv) in rects {
+                    self.builder.push_rect(
+                        bounds,
+                        FillStyle::Texture(self.ui_state.textures[image.0], uv, self.ui_state.image_renderings[image.0]),
+                    );
+                }
+            }
+            BackgroundImage::NineSlice {
+                image,
+                slice,
+                widths,
+                repeat,
+                fill,
+            } => {
+                let (width, height) = self.ui_state.image_sizes[image.0];
+                let rects = background::nine_slice_rects((width as f32, height as f32), *slice, *widths, *repeat, *fill, self.current_bounds);
+
+                for (bounds, uv) in rects {
+                    self.builder.push_rect(
+                        bounds,
+                        FillStyle::Texture(self.ui_state.textures[image.0], uv, self.ui_state.image_renderings[image.0]),
+                    );
+                }
+            }
             BackgroundImage::LinearGradient {} => println!("TODO: render linear gradient"),
             BackgroundImage::RadialGradient {} => println!("TODO: render radial gradient"),
         }
     }
 
+    fn background_box(&self, background_box: BackgroundBox, border: Option<&Border>) -> Bounds {
+        let Bounds { a, b } = self.current_bounds;
+
+        match (background_box, border) {
+            (BackgroundBox::PaddingBox, Some(border)) => {
+                let width = |side: Option<BorderSide>| match side {
+                    Some(BorderSide { width, style, .. }) if style != BorderStyle::None => width,
+                    _ => 0.,
+                };
+
+                Bounds {
+                    a: Pos {
+                        x: a.x + width(border.left),
+                        y: a.y + width(border.top),
+                    },
+                    b: Pos {
+                        x: b.x - width(border.right),
+                        y: b.y - width(border.bottom),
+                    },
+                }
+            }
+            _ => self.current_bounds,
+        }
+    }
+
     fn render_inset_shadow(&mut self, shadow: &InsetShadow) {
         println!("TODO: render_inset_shadow");
     }
@@ -362,77 +960,19 @@ impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<
     }
 
     //fn render_text_shadow(&mut self) {}
-
-    fn render_border(&mut self, border: &Border) {
-        // note the border is always inside (it acts like padding in layout)
-
-        // TODO: border_radius
-
-        // TODO: corners (overdraw will be visible with alpha colors)
-        // TODO: different edge colors (push_triangle)
-
-        let Bounds { a, b } = self.current_bounds;
-
-        if let Some(BorderSide { width, style, color }) = border.top {
-            if style == BorderStyle::Solid {
-                self.builder.push_rect(
-                    Bounds {
-                        a,
-                        b: Pos { x: b.x, y: a.y + width },
-                    },
-                    FillStyle::SolidColor(color),
-                )
-            }
-        }
-
-        if let Some(BorderSide { width, style, color }) = border.right {
-            if style == BorderStyle::Solid {
-                self.builder.push_rect(
-                    Bounds {
-                        a: Pos { x: b.x - width, y: a.y },
-                        b,
-                    },
-                    FillStyle::SolidColor(color),
-                )
-            }
-        }
-
-        if let Some(BorderSide { width, style, color }) = border.bottom {
-            if style == BorderStyle::Solid {
-                self.builder.push_rect(
-                    Bounds {
-                        a: Pos { x: a.x, y: b.y - width },
-                        b,
-                    },
-                    FillStyle::SolidColor(color),
-                )
-            }
-        }
-
-        if let Some(BorderSide { width, style, color }) = border.left {
-            if style == BorderStyle::Solid {
-                self.builder.push_rect(
-                    Bounds {
-                        a,
-                        b: Pos { x: a.x + width, y: b.y },
-                    },
-                    FillStyle::SolidColor(color),
-                )
-            }
-        }
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn empty_container() {
         let mut r = create_test_renderer();
         let c = r.create_container(0);
 
-        r.render_container(c, &vec![Bounds::ZERO]);
+        r.render_container(c, &vec![Bounds::ZERO]).unwrap();
 
         assert_eq!(r.backend.log, vec!["create_layer", "rebuild_layer 1", "render_layer 1"]);
     }
@@ -446,46 +986,139 @@ mod tests {
             c,
             Some(Outline {
                 width: 1.,
+                offset: 0.,
                 style: OutlineStyle::Solid,
                 color: Color::BLUE,
             }),
-        );
+        )
+        .unwrap();
         r.render_container(
             c,
             &vec![Bounds {
                 a: Pos::ZERO,
                 b: Pos { x: 100., y: 100. },
             }],
-        );
+        )
+        .unwrap();
 
         assert_eq!(
             r.backend.log,
             vec![
                 "create_layer",
                 "rebuild_layer 1",
-                "push_rect Bounds((0.0, 0.0), (101.0, -1.0)) SolidColor(#0000ff)",
-                "push_rect Bounds((101.0, 0.0), (100.0, 101.0)) SolidColor(#0000ff)",
-                "push_rect Bounds((-1.0, 101.0), (100.0, 100.0)) SolidColor(#0000ff)",
-                "push_rect Bounds((-1.0, -1.0), (0.0, 100.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-1.0, -1.0), (101.0, 0.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((100.0, 0.0), (101.0, 100.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-1.0, 100.0), (101.0, 101.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-1.0, 0.0), (0.0, 100.0)) SolidColor(#0000ff)",
                 "render_layer 1"
             ]
         );
     }
 
     #[test]
-    fn background_color() {
+    fn outline_styles() {
         let mut r = create_test_renderer();
         let c = r.create_container(0);
+        let bounds = vec![Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 10., y: 10. },
+        }];
 
-        r.set_background_color(c, Color::GREEN);
-        r.render_container(
+        r.set_outline(
             c,
-            &vec![Bounds {
-                a: Pos::ZERO,
-                b: Pos { x: 100., y: 100. },
-            }],
-        );
-
+            Some(Outline {
+                width: 3.,
+                offset: 2.,
+                style: OutlineStyle::Double,
+                color: Color::BLUE,
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "rebuild_layer 1",
+                "push_rect Bounds((-5.0, -5.0), (15.0, -4.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((14.0, -4.0), (15.0, 14.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-5.0, 14.0), (15.0, 15.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-5.0, -4.0), (-4.0, 14.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-3.0, -3.0), (13.0, -2.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((12.0, -2.0), (13.0, 12.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-3.0, 12.0), (13.0, 13.0)) SolidColor(#0000ff)",
+                "push_rect Bounds((-3.0, -2.0), (-2.0, 12.0)) SolidColor(#0000ff)",
+                "render_layer 1"
+            ]
+        );
+
+        // rounded
+        r.backend.log.clear();
+        r.set_outline(
+            c,
+            Some(Outline {
+                width: 2.,
+                offset: 0.,
+                style: OutlineStyle::Solid,
+                color: Color::BLUE,
+            }),
+        )
+        .unwrap();
+        r.set_border_radius(
+            c,
+            Some(BorderRadius {
+                top_left: 4.,
+                top_right: 4.,
+                bottom_right: 4.,
+                bottom_left: 4.,
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        let count = |log: &[String], prefix| log.iter().filter(|op| op.starts_with(prefix)).count();
+        assert_eq!(count(&r.backend.log, "push_rect"), 4);
+        assert_eq!(count(&r.backend.log, "push_triangle"), 4 * 6);
+        assert_eq!(r.backend.log[1], "push_rect Bounds((4.0, -2.0), (6.0, 0.0)) SolidColor(#0000ff)");
+
+        // mixed, square corners are not rounded
+        r.backend.log.clear();
+        r.set_border_radius(
+            c,
+            Some(BorderRadius {
+                top_left: 4.,
+                top_right: 0.,
+                bottom_right: 0.,
+                bottom_left: 0.,
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        assert_eq!(count(&r.backend.log, "push_triangle"), 6);
+        assert!(r.backend.log.contains(&"push_rect Bounds((10.0, -2.0), (12.0, 0.0)) SolidColor(#0000ff)".to_string()));
+        assert!(r
+            .backend
+            .log
+            .contains(&"push_rect Bounds((10.0, 10.0), (12.0, 12.0)) SolidColor(#0000ff)".to_string()));
+    }
+
+    #[test]
+    fn background_color() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+
+        r.set_background_color(c, Color::GREEN).unwrap();
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 100. },
+            }],
+        )
+        .unwrap();
+
         assert_eq!(
             r.backend.log,
             vec![
@@ -503,9 +1136,9 @@ mod tests {
         let parent = r.create_container(0);
         let child = r.create_container(1);
 
-        r.insert_child(parent, 0, Child::Container(child));
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
 
-        r.set_background_color(child, Color { r: 255, g: 0, b: 0, a: 255 });
+        r.set_background_color(child, Color { r: 255, g: 0, b: 0, a: 255 }).unwrap();
 
         r.render_container(
             parent,
@@ -519,7 +1152,8 @@ mod tests {
                     b: Pos { x: 150., y: 150. },
                 },
             ],
-        );
+        )
+        .unwrap();
 
         assert_eq!(
             r.backend.log,
@@ -532,13 +1166,562 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filters() {
+        let mut r = create_test_renderer();
+        let parent = r.create_container(0);
+        let child = r.create_container(1);
+
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
+        r.set_background_color(child, Color::RED).unwrap();
+        r.set_filters(child, vec![Filter::Blur(2.), Filter::Grayscale(1.)]).unwrap();
+        r.set_backdrop_filters(child, vec![Filter::Blur(5.)]).unwrap();
+        r.set_blend_mode(child, BlendMode::Multiply).unwrap();
+        r.set_border_radius(
+            child,
+            Some(BorderRadius {
+                top_left: 20.,
+                top_right: 0.,
+                bottom_right: 0.,
+                bottom_left: 0.,
+            }),
+        )
+        .unwrap();
+
+        r.render_container(
+            parent,
+            &vec![
+                Bounds::ZERO,
+                Bounds {
+                    a: Pos::ZERO,
+                    b: Pos { x: 10., y: 10. },
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "create_snapshot_texture",
+                "create_layer",
+                "rebuild_layer 1",
+                "push_layer 3 (0.0, 0.0)",
+                "rebuild_layer 3",
+                // outside of the groups, clipped (radius is scaled down to fit) but blurred with everything around
+                "push_clip_path [MoveTo((10.0, 0.0)), LineTo((10.0, 0.0)), CubicTo((10.0, 0.0), (10.0, 0.0), (10.0, 0.0)), LineTo((10.0, 10.0)), CubicTo((10.0, 10.0), (10.0, 10.0), (10.0, 10.0)), LineTo((0.0, 10.0)), CubicTo((0.0, 10.0), (0.0, 10.0), (0.0, 10.0)), LineTo((0.0, 10.0)), CubicTo((0.0, 4.4771523), (4.4771523, 0.0), (10.0, 0.0)), Close]",
+                "push_snapshot Bounds((-15.0, -15.0), (25.0, 25.0)) 2",
+                "push_filters [Blur(5.0)]",
+                "push_rect Bounds((-15.0, -15.0), (25.0, 25.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Pixelated)",
+                "pop_filters",
+                "pop_clip_path",
+                "push_blend_mode Multiply",
+                "push_filters [Blur(2.0), Grayscale(1.0)]",
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) SolidColor(#ff0000)",
+                "pop_filters",
+                "pop_blend_mode",
+                "render_layer 1"
+            ]
+        );
+
+        // snapshot texture is only kept while it's needed
+        r.backend.log.clear();
+        r.set_backdrop_filters(child, vec![Filter::Invert(1.)]).unwrap();
+        r.set_backdrop_filters(child, Vec::new()).unwrap();
+        assert_eq!(r.backend.log, vec!["delete_texture 2"]);
+    }
+
+    #[test]
+    fn clip_path_and_mask() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+
+        r.set_background_color(c, Color::RED).unwrap();
+        r.set_clip_path(c, Some(ClipPath::Polygon(vec![Pos::ZERO, Pos { x: 10., y: 0. }, Pos { x: 0., y: 10. }])))
+            .unwrap();
+        r.set_mask(
+            c,
+            Some(Mask::LinearGradient {
+                angle: 90.,
+                stops: vec![(0., Color::BLACK), (1., Color::TRANSPARENT)],
+            }),
+        )
+        .unwrap();
+
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos { x: 5., y: 5. },
+                b: Pos { x: 15., y: 15. },
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "create_texture 64 64",
+                "rebuild_layer 1",
+                "push_mask Bounds((5.0, 5.0), (15.0, 15.0)) 2",
+                "push_clip_path [MoveTo((5.0, 5.0)), LineTo((15.0, 5.0)), LineTo((5.0, 15.0)), Close]",
+                "push_rect Bounds((5.0, 5.0), (15.0, 15.0)) SolidColor(#ff0000)",
+                "pop_clip_path",
+                "pop_mask",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn z_index() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let flow = r.create_container(1);
+        let below = r.create_container(2);
+        let above = r.create_container(3);
+        let translucent = r.create_container(4);
+
+        r.insert_child(root, 0, Child::Container(flow)).unwrap();
+        r.insert_child(root, 1, Child::Container(below)).unwrap();
+        r.insert_child(root, 2, Child::Container(translucent)).unwrap();
+        // hoisted from the flow container
+        r.insert_child(flow, 0, Child::Container(above)).unwrap();
+
+        r.set_background_color(root, Color::WHITE).unwrap();
+        r.set_background_color(flow, Color::RED).unwrap();
+        r.set_background_color(below, Color::GREEN).unwrap();
+        r.set_background_color(above, Color::BLUE).unwrap();
+        r.set_background_color(translucent, Color::YELLOW).unwrap();
+
+        r.set_z_index(below, Some(-1)).unwrap();
+        r.set_z_index(above, Some(1)).unwrap();
+        r.set_opacity(translucent, 0.5).unwrap();
+
+        let bounds = vec![
+            Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 100. },
+            },
+            Bounds {
+                a: Pos { x: 10., y: 10. },
+                b: Pos { x: 50., y: 50. },
+            },
+            Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 100. },
+            },
+            Bounds {
+                a: Pos { x: 5., y: 5. },
+                b: Pos { x: 15., y: 15. },
+            },
+            Bounds {
+                a: Pos { x: 60., y: 60. },
+                b: Pos { x: 70., y: 70. },
+            },
+        ];
+
+        r.render_container(root, &bounds).unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "create_layer",
+                "create_layer",
+                "create_layer",
+                "rebuild_layer 1",
+                "push_rect Bounds((0.0, 0.0), (100.0, 100.0)) SolidColor(#ffffff)",
+                "push_layer 2 (0.0, 0.0)",
+                "push_rect Bounds((10.0, 10.0), (50.0, 50.0)) SolidColor(#ff0000)",
+                "push_layer 3 (60.0, 60.0)",
+                "push_layer 4 (15.0, 15.0)",
+                "rebuild_layer 2",
+                "push_rect Bounds((0.0, 0.0), (100.0, 100.0)) SolidColor(#00ff00)",
+                "rebuild_layer 3",
+                "push_filters [Opacity(0.5)]",
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) SolidColor(#ffff00)",
+                "pop_filters",
+                "rebuild_layer 4",
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) SolidColor(#0000ff)",
+                "render_layer 1"
+            ]
+        );
+
+        let hit = |x, y| r.hit_test(root, &bounds, Pos { x, y }).unwrap();
+
+        assert_eq!(hit(20., 20.), Some(above));
+        assert_eq!(hit(12., 12.), Some(flow));
+        assert_eq!(hit(65., 65.), Some(translucent));
+        assert_eq!(hit(90., 90.), Some(below));
+        assert_eq!(hit(200., 200.), None);
+    }
+
+    #[test]
+    fn hit_test_clip() {
+        let mut r = create_test_renderer();
+        let [root, clipped, clipped_child, hidden, hidden_child] = [0, 1, 2, 3, 4].map(|i| r.create_container(i));
+
+        r.insert_child(root, 0, Child::Container(clipped)).unwrap();
+        r.insert_child(clipped, 0, Child::Container(clipped_child)).unwrap();
+        r.insert_child(root, 1, Child::Container(hidden)).unwrap();
+        r.insert_child(hidden, 0, Child::Container(hidden_child)).unwrap();
+        r.set_clip_path(
+            clipped,
+            Some(ClipPath::Circle {
+                center: Pos { x: 10., y: 10. },
+                radius: 10.,
+            }),
+        )
+        .unwrap();
+        r.set_overflow(hidden, Overflow::Hidden).unwrap();
+
+        let rect = |x: f32, y: f32, size: f32| Bounds {
+            a: Pos { x, y },
+            b: Pos { x: x + size, y: y + size },
+        };
+        // children are relative to their parents
+        let bounds = vec![rect(0., 0., 100.), rect(0., 0., 20.), rect(10., 10., 30.), rect(50., 50., 10.), rect(5., 5., 20.)];
+        let hit = |x, y| r.hit_test(root, &bounds, Pos { x, y }).unwrap();
+
+        assert_eq!(hit(10., 10.), Some(clipped));
+        assert_eq!(hit(16., 16.), Some(clipped_child));
+        // outside of the circle
+        assert_eq!(hit(1., 1.), Some(root));
+        assert_eq!(hit(19., 19.), Some(root));
+        assert_eq!(hit(30., 30.), Some(root));
+
+        // overflow is painted (not clipped yet), so it can be hit too
+        assert_eq!(hit(57., 57.), Some(hidden_child));
+        assert_eq!(hit(52., 52.), Some(hidden));
+        assert_eq!(hit(70., 70.), Some(hidden_child));
+    }
+
+    #[test]
+    fn layer_caching() {
+        let mut r = create_test_renderer();
+        let parent = r.create_container(0);
+        let child = r.create_container(1);
+
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
+        r.set_background_color(parent, Color::WHITE).unwrap();
+        r.set_background_color(child, Color::RED).unwrap();
+        r.set_z_index(child, Some(1)).unwrap();
+
+        let mut bounds = vec![
+            Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 100. },
+            },
+            Bounds {
+                a: Pos { x: 10., y: 10. },
+                b: Pos { x: 20., y: 20. },
+            },
+        ];
+
+        r.render_container(parent, &bounds).unwrap();
+        r.backend.log.clear();
+
+        // nothing has changed
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.backend.log, vec!["render_layer 1"]);
+        r.backend.log.clear();
+
+        // only the child layer
+        r.set_background_color(child, Color::BLUE).unwrap();
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(
+            r.backend.log,
+            vec!["rebuild_layer 2", "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) SolidColor(#0000ff)", "render_layer 1"]
+        );
+        r.backend.log.clear();
+
+        // moved, only the parent
+        bounds[1] = bounds[1].translate(Pos { x: 5., y: 0. });
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "rebuild_layer 1",
+                "push_rect Bounds((0.0, 0.0), (100.0, 100.0)) SolidColor(#ffffff)",
+                "push_layer 2 (15.0, 10.0)",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn damage() {
+        let mut r = create_test_renderer();
+        let parent = r.create_container(0);
+        let child = r.create_container(1);
+
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
+        r.set_background_color(child, Color::RED).unwrap();
+
+        let mut bounds = vec![
+            Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 100. },
+            },
+            Bounds {
+                a: Pos { x: 10., y: 10. },
+                b: Pos { x: 20., y: 20. },
+            },
+        ];
+
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[0]]);
+
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.damage(), &[]);
+
+        // including the outline
+        r.set_outline(
+            child,
+            Some(Outline {
+                width: 1.,
+                offset: 1.,
+                style: OutlineStyle::Solid,
+                color: Color::BLACK,
+            }),
+        )
+        .unwrap();
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[1].inflate_uniform(2.)]);
+
+        // old & new
+        bounds[1] = bounds[1].translate(Pos { x: 50., y: 0. });
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.damage().len(), 2);
+        assert_eq!(r.damage()[1], bounds[1].inflate_uniform(2.));
+
+        // merged with the (dirty) parent
+        r.remove_child(parent, Child::Container(child)).unwrap();
+        r.render_container(parent, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[0]]);
+    }
+
+    #[test]
+    fn filtered_damage() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let blurred = r.create_container(1);
+        let child = r.create_container(2);
+        let sibling = r.create_container(3);
+        let backdrop = r.create_container(4);
+
+        r.insert_child(root, 0, Child::Container(blurred)).unwrap();
+        r.insert_child(blurred, 0, Child::Container(child)).unwrap();
+        r.insert_child(root, 1, Child::Container(sibling)).unwrap();
+        r.insert_child(root, 2, Child::Container(backdrop)).unwrap();
+        r.set_filters(blurred, vec![Filter::Blur(2.)]).unwrap();
+        r.set_backdrop_filters(backdrop, vec![Filter::Blur(1.)]).unwrap();
+
+        let rect = |x: f32, y: f32, size: f32| Bounds {
+            a: Pos { x, y },
+            b: Pos { x: x + size, y: y + size },
+        };
+        let bounds = vec![rect(0., 0., 100.), rect(0., 0., 50.), rect(10., 10., 10.), rect(0., 60., 10.), rect(12., 60., 8.)];
+        r.render_container(root, &bounds).unwrap();
+
+        // blurred by the parent
+        r.set_background_color(child, Color::RED).unwrap();
+        r.render_container(root, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[2].inflate_uniform(6.)]);
+
+        // close enough to be blurred into the backdrop (which is redrawn with everything it samples)
+        r.set_background_color(sibling, Color::RED).unwrap();
+        r.render_container(root, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[3].union(&bounds[4].inflate_uniform(3.))]);
+    }
+
+    #[test]
+    fn culling() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let visible = r.create_container(1);
+        let outlined = r.create_container(2);
+        let offscreen = r.create_container(3);
+        let positioned = r.create_container(4);
+
+        for (i, ch) in [visible, outlined, offscreen, positioned].iter().enumerate() {
+            r.insert_child(root, i, Child::Container(*ch)).unwrap();
+            r.set_background_color(*ch, Color::RED).unwrap();
+        }
+
+        // reaches into the viewport
+        r.set_outline(
+            outlined,
+            Some(Outline {
+                width: 10.,
+                offset: 0.,
+                style: OutlineStyle::Solid,
+                color: Color::BLACK,
+            }),
+        )
+        .unwrap();
+        r.set_z_index(positioned, Some(1)).unwrap();
+        r.set_viewport(Some(Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 100., y: 100. },
+        }));
+
+        let rect = |x: f32| Bounds {
+            a: Pos { x, y: 0. },
+            b: Pos { x: x + 10., y: 10. },
+        };
+        r.render_container(root, &vec![rect(0.), rect(50.), rect(105.), rect(300.), rect(400.)]).unwrap();
+
+        assert_eq!((r.stats().rendered, r.stats().culled), (3, 2));
+        assert!(!r.backend.log.iter().any(|op| op.contains("(300.0") || op.contains("push_layer")));
+    }
+
+    #[test]
+    fn filters_extend_cull() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let near = r.create_container(1);
+        let far = r.create_container(2);
+
+        r.insert_child(root, 0, Child::Container(near)).unwrap();
+        r.insert_child(root, 1, Child::Container(far)).unwrap();
+        r.set_background_color(near, Color::RED).unwrap();
+        r.set_background_color(far, Color::RED).unwrap();
+        r.set_filters(root, vec![Filter::Blur(5.)]).unwrap();
+        r.set_viewport(Some(Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 100., y: 100. },
+        }));
+
+        let rect = |x: f32| Bounds {
+            a: Pos { x, y: 0. },
+            b: Pos { x: x + 10., y: 10. },
+        };
+        r.render_container(root, &vec![rect(0.), rect(105.), rect(120.)]).unwrap();
+
+        // near one is blurred into the viewport
+        assert_eq!(r.stats().culled, 1);
+        assert!(r.backend.log.iter().any(|op| op.contains("(105.0")));
+    }
+
+    #[test]
+    fn overflow_is_not_culled() {
+        let mut r = create_test_renderer();
+        let parent = r.create_container(0);
+        let child = r.create_container(1);
+
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
+        r.set_background_color(child, Color::RED).unwrap();
+        r.set_overflow(parent, Overflow::Hidden).unwrap();
+
+        let rect = |x: f32| Bounds {
+            a: Pos { x, y: 0. },
+            b: Pos { x: x + 10., y: 10. },
+        };
+        r.render_container(parent, &vec![rect(0.), rect(20.)]).unwrap();
+
+        // overflow is not clipped yet, so it's still visible
+        assert_eq!(r.stats().culled, 0);
+        assert!(r.backend.log.iter().any(|op| op.contains("(20.0")));
+    }
+
+    #[test]
+    fn occlusion_culling() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let panel = r.create_container(1);
+        let translucent = r.create_container(2);
+
+        r.insert_child(root, 0, Child::Container(panel)).unwrap();
+        r.insert_child(root, 1, Child::Container(translucent)).unwrap();
+        r.set_background_color(root, Color::WHITE).unwrap();
+        r.set_background_color(panel, Color::RED).unwrap();
+        r.set_background_color(translucent, Color { r: 0, g: 0, b: 255, a: 128 }).unwrap();
+        r.set_occlusion_culling(true);
+
+        let rect = |x: f32| Bounds {
+            a: Pos { x, y: 0. },
+            b: Pos { x: 100., y: 100. },
+        };
+        r.render_container(root, &vec![rect(0.), rect(0.), rect(50.)]).unwrap();
+
+        // translucent doesn't hide anything
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "rebuild_layer 1",
+                "push_rect Bounds((0.0, 0.0), (100.0, 100.0)) SolidColor(#ff0000)",
+                "push_rect Bounds((50.0, 0.0), (100.0, 100.0)) SolidColor(#0000ff#80)",
+                "render_layer 1"
+            ]
+        );
+        assert_eq!(r.stats().occluded, 1);
+
+        // child context in between could see it through its backdrop
+        let context = r.create_container(3);
+        r.insert_child(root, 0, Child::Container(context)).unwrap();
+        r.set_z_index(context, Some(-1)).unwrap();
+        r.render_container(root, &vec![rect(0.), rect(0.), rect(50.), rect(0.)]).unwrap();
+
+        assert_eq!(r.stats().occluded, 0);
+    }
+
+    #[test]
+    fn batching() {
+        let mut r = create_test_renderer();
+        let root = r.create_container(0);
+        let image = r.create_image(1, 1, Box::new([0; 4])).unwrap();
+
+        for i in 0..4 {
+            let ch = r.create_container(i + 1);
+            r.insert_child(root, i, Child::Container(ch)).unwrap();
+
+            if i == 1 {
+                r.set_background_images(
+                    ch,
+                    vec![BackgroundImage::Image {
+                        image,
+                        size: BackgroundSize::Explicit(Some(10.), Some(10.)),
+                        position: BackgroundPosition::TOP_LEFT,
+                        repeat: (BackgroundRepeat::NoRepeat, BackgroundRepeat::NoRepeat),
+                        origin: BackgroundBox::BorderBox,
+                        clip: BackgroundBox::BorderBox,
+                    }],
+                )
+                .unwrap();
+            } else {
+                r.set_background_color(ch, Color::RED).unwrap();
+            }
+        }
+
+        let rect = |x: f32| Bounds {
+            a: Pos { x, y: 0. },
+            b: Pos { x: x + 10., y: 10. },
+        };
+        r.render_container(root, &vec![Bounds::ZERO, rect(0.), rect(20.), rect(40.), rect(50.)]).unwrap();
+
+        // image doesn't overlap, so the rects can go before it (and the last two are adjacent)
+        assert_eq!(
+            r.backend.log[3..6],
+            [
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) SolidColor(#ff0000)",
+                "push_rect Bounds((40.0, 0.0), (60.0, 10.0)) SolidColor(#ff0000)",
+                "push_rect Bounds((20.0, 0.0), (30.0, 10.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+            ]
+        );
+        assert_eq!((r.stats().batches, r.stats().merged), (2, 1));
+    }
+
     #[test]
     fn it_works() {
         let mut r = create_test_renderer();
         let c = r.create_container(0);
 
-        r.set_overflow(c, Overflow::Visible);
-        r.set_opacity(c, 0.5);
+        r.set_overflow(c, Overflow::Visible).unwrap();
+        r.set_opacity(c, 0.5).unwrap();
         r.set_border_radius(
             c,
             Some(BorderRadius {
@@ -547,7 +1730,8 @@ mod tests {
                 bottom_right: 5.,
                 bottom_left: 5.,
             }),
-        );
+        )
+        .unwrap();
         r.set_outline_shadows(
             c,
             vec![OutlineShadow {
@@ -556,16 +1740,19 @@ mod tests {
                 spread: 5.,
                 color: Color::BLACK,
             }],
-        );
+        )
+        .unwrap();
         r.set_outline(
             c,
             Some(Outline {
                 width: 1.,
+                offset: 0.,
                 style: OutlineStyle::Solid,
                 color: Color::BLACK,
             }),
-        );
-        r.set_background_color(c, Color::BLACK);
+        )
+        .unwrap();
+        r.set_background_color(c, Color::BLACK).unwrap();
         r.set_inset_shadows(
             c,
             vec![InsetShadow {
@@ -574,8 +1761,9 @@ mod tests {
                 spread: 5.,
                 color: Color::BLACK,
             }],
-        );
-        r.set_color(c, Color::BLACK);
+        )
+        .unwrap();
+        r.set_color(c, Color::BLACK).unwrap();
         r.set_border(
             c,
             Some(Border {
@@ -600,37 +1788,500 @@ mod tests {
                     color: Color::YELLOW,
                 }),
             }),
-        );
+        )
+        .unwrap();
 
-        r.render_container(c, &vec![Bounds::ZERO]);
+        r.render_container(c, &vec![Bounds::ZERO]).unwrap();
 
         assert_eq!(
             r.backend.log,
             vec![
                 "create_layer",
                 "rebuild_layer 1",
+                "push_filters [Opacity(0.5)]",
                 "push_rect Bounds((-5.0, -5.0), (5.0, 5.0)) SolidColor(#000000)",
-                "push_rect Bounds((0.0, 0.0), (1.0, -1.0)) SolidColor(#000000)",
-                "push_rect Bounds((1.0, 0.0), (0.0, 1.0)) SolidColor(#000000)",
-                "push_rect Bounds((-1.0, 1.0), (0.0, 0.0)) SolidColor(#000000)",
-                "push_rect Bounds((-1.0, -1.0), (0.0, 0.0)) SolidColor(#000000)",
+                // merged
+                "push_rect Bounds((-1.0, -1.0), (1.0, 1.0)) SolidColor(#000000)",
                 "push_rect Bounds((0.0, 0.0), (0.0, 0.0)) SolidColor(#000000)",
-                "push_rect Bounds((0.0, 0.0), (0.0, 1.0)) SolidColor(#ff0000)",
-                "push_rect Bounds((-1.0, 0.0), (0.0, 0.0)) SolidColor(#00ff00)",
-                "push_rect Bounds((0.0, -1.0), (0.0, 0.0)) SolidColor(#0000ff)",
-                "push_rect Bounds((0.0, 0.0), (1.0, 0.0)) SolidColor(#ffff00)",
+                "push_triangle (0.0, 0.0) (1.0, 0.0) (1.0, 1.0) #ff0000",
+                "push_triangle (-1.0, 0.0) (0.0, 0.0) (-1.0, 1.0) #ff0000",
+                "push_triangle (0.0, 0.0) (0.0, 1.0) (-1.0, 1.0) #00ff00",
+                "push_triangle (-1.0, -1.0) (0.0, -1.0) (0.0, 0.0) #00ff00",
+                "push_triangle (0.0, 0.0) (1.0, -1.0) (1.0, 0.0) #0000ff",
+                "push_triangle (-1.0, 0.0) (-1.0, -1.0) (0.0, 0.0) #0000ff",
+                "push_triangle (0.0, 0.0) (1.0, 1.0) (0.0, 1.0) #ffff00",
+                "push_triangle (0.0, -1.0) (1.0, -1.0) (0.0, 0.0) #ffff00",
+                "pop_filters",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn image_data() {
+        let mut r = create_test_renderer::<usize>();
+        let img = r.create_image(2, 2, vec![0; 16].into_boxed_slice()).unwrap();
+
+        r.set_image_data(img, 2, 2, vec![1; 16].into_boxed_slice()).unwrap();
+        r.set_image_sub_data(img, 1, 1, 1, 1, &[2, 2, 2, 2]).unwrap();
+        assert_eq!(&*r.backend.textures[&2], &[1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2]);
+
+        // resize
+        r.set_image_data(img, 1, 1, vec![3; 4].into_boxed_slice()).unwrap();
+        r.set_image_sub_data(img, 0, 0, 1, 1, &[4, 4, 4, 4]).unwrap();
+        assert_eq!(&*r.backend.textures[&5], &[4, 4, 4, 4]);
+
+        let c = r.create_container(0);
+        r.set_background_images(
+            c,
+            vec![BackgroundImage::Image {
+                image: img,
+                size: BackgroundSize::Fill,
+                position: BackgroundPosition::TOP_LEFT,
+                repeat: (BackgroundRepeat::NoRepeat, BackgroundRepeat::NoRepeat),
+                origin: BackgroundBox::BorderBox,
+                clip: BackgroundBox::BorderBox,
+            }],
+        )
+        .unwrap();
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 10., y: 10. },
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "create_texture 2 2",
+                "update_texture 2",
+                "update_texture 2",
+                "create_texture 1 1",
+                "delete_texture 2",
+                "update_texture 5",
+                "rebuild_layer 1",
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) Texture(5, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut r = create_test_renderer::<usize>();
+        let c = r.create_container(0);
+        let text = r.create_text(1);
+        let img = r.create_image(1, 1, vec![0; 4].into_boxed_slice()).unwrap();
+
+        // handles from somewhere else (out of range here)
+        let mut other = create_test_renderer::<usize>();
+        other.create_container(0);
+        other.create_text(0);
+        let (foreign, foreign_text) = (other.create_container(0), other.create_text(0));
+        let foreign_img = (0..2).map(|_| other.create_image(1, 1, vec![0; 4].into_boxed_slice()).unwrap()).last().unwrap();
+
+        assert!(matches!(r.set_opacity(foreign, 0.5), Err(RenderError::InvalidContainer(_))));
+        assert!(matches!(r.insert_child(foreign, 0, Child::Text(text)), Err(RenderError::InvalidContainer(_))));
+        assert!(matches!(r.insert_child(c, 0, Child::Container(foreign)), Err(RenderError::InvalidContainer(_))));
+        assert!(matches!(r.insert_child(c, 0, Child::Text(foreign_text)), Err(RenderError::InvalidText(_))));
+        assert!(matches!(r.set_text_data(foreign_text, "".to_string()), Err(RenderError::InvalidText(_))));
+        assert!(matches!(r.set_mask(c, Some(Mask::Image(foreign_img))), Err(RenderError::InvalidImage(_))));
+        assert!(matches!(
+            r.set_image_rendering(foreign_img, ImageRendering::Pixelated),
+            Err(RenderError::InvalidImage(_))
+        ));
+        assert!(matches!(r.render_container(foreign, &vec![Bounds::ZERO]), Err(RenderError::InvalidContainer(_))));
+        assert!(matches!(r.hit_test(foreign, &vec![Bounds::ZERO], Pos::ZERO), Err(RenderError::InvalidContainer(_))));
+
+        // out of range index
+        assert!(matches!(
+            r.insert_child(c, 1, Child::Text(text)),
+            Err(RenderError::IndexOutOfRange { index: 1, len: 0 })
+        ));
+        r.insert_child(c, 0, Child::Text(text)).unwrap();
+        r.insert_child(c, 1, Child::Text(text)).unwrap();
+
+        // data
+        assert!(matches!(
+            r.create_image(2, 2, vec![0; 4].into_boxed_slice()),
+            Err(RenderError::InvalidImageData { len: 4, .. })
+        ));
+        assert!(matches!(
+            r.set_image_data(img, 2, 1, vec![0; 4].into_boxed_slice()),
+            Err(RenderError::InvalidImageData { .. })
+        ));
+        assert!(matches!(r.set_image_sub_data(img, 1, 0, 1, 1, &[0; 4]), Err(RenderError::RectOutOfImage)));
+        assert!(matches!(r.set_image_sub_data(img, -1, 0, 1, 1, &[0; 4]), Err(RenderError::RectOutOfImage)));
+        assert!(matches!(r.set_image_sub_data(img, i32::MAX, 0, 1, 1, &[0; 4]), Err(RenderError::RectOutOfImage)));
+        r.set_image_sub_data(img, 0, 0, 0, 0, &[]).unwrap();
+        r.set_image_sub_data(img, 1, 1, 0, 0, &[]).unwrap();
+        assert!(matches!(
+            r.create_image_from_encoded(b"hello"),
+            Err(RenderError::Decoding(DecodingError::UnknownFormat))
+        ));
+        assert!(matches!(r.create_animated_image(b"GIF89a"), Err(RenderError::Decoding(_))));
+
+        // nothing has changed
+        assert_eq!(r.ui_state.textures.len(), 1);
+        assert_eq!(r.ui_state.opacities, vec![1.]);
+
+        // backend failure, damage is kept for the next time
+        let bounds = vec![
+            Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 10., y: 10. },
+            },
+            Bounds::ZERO,
+        ];
+        r.backend.lost = true;
+        assert!(matches!(r.render_container(c, &bounds), Err(RenderError::Backend(_))));
+
+        r.backend.lost = false;
+        r.render_container(c, &bounds).unwrap();
+        assert_eq!(r.damage(), &[bounds[0]]);
+
+        // circle texture for the dots can't be created, nothing has changed either
+        r.backend.lost = true;
+        let dotted = Some(BorderSide {
+            width: 1.,
+            style: BorderStyle::Dotted,
+            color: Color::BLUE,
+        });
+        let border = Border {
+            top: dotted,
+            right: None,
+            bottom: None,
+            left: None,
+        };
+        assert!(matches!(r.set_border(c, Some(border)), Err(RenderError::Backend(_))));
+        assert!(!r.ui_state.dirty[c.0]);
+        assert!(r.ui_state.borders[c.0].is_none());
+    }
+
+    #[test]
+    fn cycles() {
+        let mut r = create_test_renderer::<usize>();
+        let (root, parent, child) = (r.create_container(0), r.create_container(1), r.create_container(2));
+
+        r.insert_child(root, 0, Child::Container(parent)).unwrap();
+        r.insert_child(parent, 0, Child::Container(child)).unwrap();
+
+        assert!(matches!(r.insert_child(root, 0, Child::Container(root)), Err(RenderError::Cycle(_))));
+        assert!(matches!(r.insert_child(child, 0, Child::Container(root)), Err(RenderError::Cycle(_))));
+        assert_eq!(r.ui_state.children[child.0], vec![]);
+
+        // the same one can still be in more places
+        r.insert_child(root, 1, Child::Container(child)).unwrap();
+    }
+
+    #[test]
+    fn background_image() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+        let img = r.create_image(10, 20, vec![0; 800].into_boxed_slice()).unwrap();
+        let bg = |size, position, repeat, origin| BackgroundImage::Image {
+            image: img,
+            size,
+            position,
+            repeat,
+            origin,
+            clip: BackgroundBox::BorderBox,
+        };
+
+        r.set_border(
+            c,
+            Some(Border {
+                top: None,
+                right: None,
+                bottom: None,
+                left: Some(BorderSide {
+                    width: 10.,
+                    style: BorderStyle::None,
+                    color: Color::BLACK,
+                }),
+            }),
+        )
+        .unwrap();
+        r.set_background_images(
+            c,
+            vec![
+                bg(
+                    BackgroundSize::Contain,
+                    BackgroundPosition::CENTER,
+                    (BackgroundRepeat::NoRepeat, BackgroundRepeat::NoRepeat),
+                    BackgroundBox::BorderBox,
+                ),
+                bg(
+                    BackgroundSize::Auto,
+                    BackgroundPosition::TOP_LEFT,
+                    (BackgroundRepeat::Repeat, BackgroundRepeat::NoRepeat),
+                    BackgroundBox::BorderBox,
+                ),
+                bg(
+                    BackgroundSize::Explicit(Some(30.), Some(30.)),
+                    BackgroundPosition::TOP_LEFT,
+                    (BackgroundRepeat::Space, BackgroundRepeat::Round),
+                    BackgroundBox::PaddingBox,
+                ),
+            ],
+        )
+        .unwrap();
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 40. },
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            &r.backend.log[3..],
+            &[
+                "push_rect Bounds((40.0, 0.0), (60.0, 40.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+                "push_rect Bounds((0.0, 0.0), (100.0, 20.0)) Texture(2, Bounds((0.0, 0.0), (10.0, 1.0)), Smooth)",
+                "push_rect Bounds((0.0, 0.0), (30.0, 40.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+                "push_rect Bounds((35.0, 0.0), (65.0, 40.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+                "push_rect Bounds((70.0, 0.0), (100.0, 40.0)) Texture(2, Bounds((0.0, 0.0), (1.0, 1.0)), Smooth)",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn nine_slice() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+        let img = r.create_image(4, 4, vec![0; 64].into_boxed_slice()).unwrap();
+
+        r.set_image_rendering(img, ImageRendering::Pixelated).unwrap();
+
+        r.set_background_images(
+            c,
+            vec![BackgroundImage::NineSlice {
+                image: img,
+                slice: SideOffsets::uniform(1.),
+                widths: SideOffsets::uniform(10.),
+                repeat: (NineSliceRepeat::Stretch, NineSliceRepeat::Round),
+                fill: true,
+            }],
+        )
+        .unwrap();
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 100., y: 50. },
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            &r.backend.log[3..],
+            &[
+                "push_rect Bounds((0.0, 0.0), (10.0, 10.0)) Texture(2, Bounds((0.0, 0.0), (0.25, 0.25)), Pixelated)",
+                "push_rect Bounds((10.0, 0.0), (90.0, 10.0)) Texture(2, Bounds((0.25, 0.0), (0.75, 0.25)), Pixelated)",
+                "push_rect Bounds((90.0, 0.0), (100.0, 10.0)) Texture(2, Bounds((0.75, 0.0), (1.0, 0.25)), Pixelated)",
+                "push_rect Bounds((0.0, 10.0), (10.0, 25.0)) Texture(2, Bounds((0.0, 0.25), (0.25, 0.75)), Pixelated)",
+                "push_rect Bounds((0.0, 25.0), (10.0, 40.0)) Texture(2, Bounds((0.0, 0.25), (0.25, 0.75)), Pixelated)",
+                "push_rect Bounds((10.0, 10.0), (90.0, 25.0)) Texture(2, Bounds((0.25, 0.25), (0.75, 0.75)), Pixelated)",
+                "push_rect Bounds((10.0, 25.0), (90.0, 40.0)) Texture(2, Bounds((0.25, 0.25), (0.75, 0.75)), Pixelated)",
+                "push_rect Bounds((90.0, 10.0), (100.0, 25.0)) Texture(2, Bounds((0.75, 0.25), (1.0, 0.75)), Pixelated)",
+                "push_rect Bounds((90.0, 25.0), (100.0, 40.0)) Texture(2, Bounds((0.75, 0.25), (1.0, 0.75)), Pixelated)",
+                "push_rect Bounds((0.0, 40.0), (10.0, 50.0)) Texture(2, Bounds((0.0, 0.75), (0.25, 1.0)), Pixelated)",
+                "push_rect Bounds((10.0, 40.0), (90.0, 50.0)) Texture(2, Bounds((0.25, 0.75), (0.75, 1.0)), Pixelated)",
+                "push_rect Bounds((90.0, 40.0), (100.0, 50.0)) Texture(2, Bounds((0.75, 0.75), (1.0, 1.0)), Pixelated)",
+                "render_layer 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn border_styles() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+
+        let side = |width, style, color| Some(BorderSide { width, style, color });
+
+        r.set_border(
+            c,
+            Some(Border {
+                top: side(2., BorderStyle::Dashed, Color::RED),
+                right: side(3., BorderStyle::Double, Color::GREEN),
+                bottom: side(1., BorderStyle::Inset, Color::RED),
+                left: side(2., BorderStyle::Dotted, Color::BLUE),
+            }),
+        )
+        .unwrap();
+        r.render_container(
+            c,
+            &vec![Bounds {
+                a: Pos::ZERO,
+                b: Pos { x: 30., y: 10. },
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            r.backend.log,
+            vec![
+                "create_layer",
+                "create_texture 32 32",
+                "rebuild_layer 1",
+                "push_triangle (0.0, 0.0) (2.0, 0.0) (2.0, 2.0) #ff0000",
+                "push_triangle (27.0, 0.0) (30.0, 0.0) (27.0, 2.0) #ff0000",
+                "push_rect Bounds((2.0, 0.0), (6.0, 2.0)) SolidColor(#ff0000)",
+                "push_rect Bounds((12.0, 0.0), (18.0, 2.0)) SolidColor(#ff0000)",
+                "push_rect Bounds((24.0, 0.0), (27.0, 2.0)) SolidColor(#ff0000)",
+                "push_triangle (30.0, 0.0) (30.0, 0.6666667) (29.0, 0.6666667) #00ff00",
+                "push_rect Bounds((29.0, 0.6666667), (30.0, 9.666667)) SolidColor(#00ff00)",
+                "push_triangle (29.0, 9.666667) (30.0, 9.666667) (30.0, 10.0) #00ff00",
+                "push_triangle (28.0, 1.3333334) (28.0, 2.0) (27.0, 2.0) #00ff00",
+                "push_rect Bounds((27.0, 2.0), (28.0, 9.0)) SolidColor(#00ff00)",
+                "push_triangle (27.0, 9.0) (28.0, 9.0) (28.0, 9.333333) #00ff00",
+                "push_triangle (0.0, 10.0) (2.0, 9.0) (2.0, 10.0) #ff5555",
+                "push_rect Bounds((2.0, 9.0), (27.0, 10.0)) SolidColor(#ff5555)",
+                "push_triangle (27.0, 10.0) (27.0, 9.0) (30.0, 10.0) #ff5555",
+                "push_rect Bounds((0.0, 4.0), (2.0, 6.0)) Msdf { texture: 2, uv: Bounds((0.0, 0.0), (1.0, 1.0)), factor: 0.5, color: #0000ff }",
+                "push_rect Bounds((0.0, 8.0), (2.0, 10.0)) Msdf { texture: 2, uv: Bounds((0.0, 0.0), (1.0, 1.0)), factor: 0.5, color: #0000ff }",
                 "render_layer 1"
             ]
         );
     }
 
+    #[test]
+    fn rounded_border() {
+        let mut r = create_test_renderer();
+        let c = r.create_container(0);
+        let bounds = vec![Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 20., y: 10. },
+        }];
+
+        let side = |width, style| {
+            Some(BorderSide {
+                width,
+                style,
+                color: Color::BLUE,
+            })
+        };
+
+        r.set_border(
+            c,
+            Some(Border {
+                top: side(2., BorderStyle::Solid),
+                right: side(2., BorderStyle::Solid),
+                bottom: side(2., BorderStyle::Solid),
+                left: side(2., BorderStyle::Solid),
+            }),
+        )
+        .unwrap();
+        r.set_border_radius(
+            c,
+            Some(BorderRadius {
+                top_left: 4.,
+                top_right: 0.,
+                bottom_right: 0.,
+                bottom_left: 0.,
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        let count = |log: &[String], prefix| log.iter().filter(|op| op.starts_with(prefix)).count();
+        let has = |log: &[String], op: &str| log.iter().any(|o| o == op);
+
+        // straight parts start after the radius, the rounded corner is split between the two edges
+        assert_eq!(count(&r.backend.log, "push_rect"), 4);
+        assert_eq!(count(&r.backend.log, "push_triangle"), 2 * 4 + 3 * 2);
+        assert!(has(&r.backend.log, "push_rect Bounds((4.0, 0.0), (18.0, 2.0)) SolidColor(#0000ff)"));
+        assert!(has(&r.backend.log, "push_rect Bounds((0.0, 4.0), (2.0, 8.0)) SolidColor(#0000ff)"));
+        assert_eq!(r.backend.log[5], "push_triangle (3.2346332, 2.152241) (4.0, 0.0) (4.0, 2.0) #0000ff");
+        assert_eq!(r.backend.log[16], "push_triangle (2.0, 4.0) (0.0, 4.0) (0.30448198, 2.4692662) #0000ff");
+
+        // square corners are still mitered
+        assert!(has(&r.backend.log, "push_triangle (18.0, 2.0) (20.0, 0.0) (18.0, 0.0) #0000ff"));
+        assert!(has(&r.backend.log, "push_triangle (18.0, 2.0) (20.0, 2.0) (20.0, 0.0) #0000ff"));
+
+        // thicker than the radius, square inside
+        r.backend.log.clear();
+        r.set_border(
+            c,
+            Some(Border {
+                top: side(2., BorderStyle::Solid),
+                right: side(2., BorderStyle::Solid),
+                bottom: side(2., BorderStyle::Solid),
+                left: side(6., BorderStyle::Solid),
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        assert!(has(&r.backend.log, "push_rect Bounds((6.0, 0.0), (18.0, 2.0)) SolidColor(#0000ff)"));
+        assert!(has(&r.backend.log, "push_rect Bounds((0.0, 4.0), (6.0, 8.0)) SolidColor(#0000ff)"));
+        assert!(has(&r.backend.log, "push_triangle (6.0, 2.0) (4.0, 0.0) (6.0, 0.0) #0000ff"));
+        assert!(has(&r.backend.log, "push_triangle (6.0, 8.0) (0.0, 10.0) (6.0, 10.0) #0000ff"));
+
+        // two bands, each with its own arcs
+        r.backend.log.clear();
+        r.set_border(
+            c,
+            Some(Border {
+                top: side(3., BorderStyle::Double),
+                right: side(3., BorderStyle::Double),
+                bottom: side(3., BorderStyle::Double),
+                left: side(3., BorderStyle::Double),
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        assert_eq!(count(&r.backend.log, "push_rect"), 4 * 2);
+        // (outer part of a square corner is a quad, the inner one is a triangle)
+        assert_eq!(count(&r.backend.log, "push_triangle"), 2 * 2 * 4 + 3 * 2 * (2 + 1));
+
+        // dots along the arc
+        r.backend.log.clear();
+        r.set_border(
+            c,
+            Some(Border {
+                top: side(2., BorderStyle::Dotted),
+                right: side(2., BorderStyle::Dotted),
+                bottom: side(2., BorderStyle::Dotted),
+                left: side(2., BorderStyle::Dotted),
+            }),
+        )
+        .unwrap();
+        r.render_container(c, &bounds).unwrap();
+
+        let dots: Vec<_> = r.backend.log.iter().filter(|op| op.contains("Msdf")).collect();
+        assert_eq!(dots.len(), 5 + 2 + 5 + 1);
+        assert_eq!(count(&r.backend.log, "push_triangle"), 0);
+
+        // one on each half of the arc, the square corners have one dot (for the top/bottom edge)
+        assert!(dots[0].starts_with("push_rect Bounds((2.2235432, 0.10222244), (4.223543, 2.1022224))"));
+        assert!(dots[12].starts_with("push_rect Bounds((0.10222256, 2.223543), (2.1022224, 4.223543))"));
+        assert!(dots[1].starts_with("push_rect Bounds((18.0, 0.0), (20.0, 2.0))"));
+    }
+
     fn create_test_renderer<BK: Copy>() -> Renderer<TestRenderBackend, BK> {
-        Renderer::new(TestRenderBackend { log: Vec::new() })
+        Renderer::new(TestRenderBackend {
+            log: Vec::new(),
+            textures: HashMap::new(),
+            lost: false,
+        })
     }
 
     #[derive(Debug)]
     struct TestRenderBackend {
         log: Vec<String>,
+        textures: HashMap<usize, Box<[u8]>>,
+        // render_layer() & create_texture() fail
+        lost: bool,
     }
 
     impl RenderBackend for TestRenderBackend {
@@ -650,18 +2301,49 @@ mod tests {
             f(&mut self.log);
         }
 
-        fn render_layer(&mut self, layer: Self::LayerId) {
+        fn render_layer(&mut self, layer: Self::LayerId, damage: &[Bounds]) -> Result<(), RenderError> {
+            if self.lost {
+                return Err(RenderError::Backend("context lost".to_string()));
+            }
+
             self.log.push(format!("render_layer {:?}", layer));
+
+            Ok(())
         }
 
-        fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Self::TextureId {
+        fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Result<Self::TextureId, RenderError> {
+            if self.lost {
+                return Err(RenderError::Backend("context lost".to_string()));
+            }
+
+            error::check_image_data(width, height, &data)?;
+
             self.log.push(format!("create_texture {:?} {:?}", width, height));
+            self.textures.insert(self.log.len(), data);
 
-            self.log.len()
+            Ok(self.log.len())
         }
 
-        fn update_texture(&mut self, texture: Self::TextureId, f: impl FnMut(&mut [u8])) {
+        fn update_texture(&mut self, texture: Self::TextureId, mut f: impl FnMut(&mut [u8])) {
             self.log.push(format!("update_texture {:?}", texture));
+
+            f(self.textures.get_mut(&texture).unwrap());
+        }
+
+        fn create_snapshot_texture(&mut self) -> Result<Self::TextureId, RenderError> {
+            if self.lost {
+                return Err(RenderError::Backend("context lost".to_string()));
+            }
+
+            self.log.push("create_snapshot_texture".to_string());
+            self.textures.insert(self.log.len(), Box::new([]));
+
+            Ok(self.log.len())
+        }
+
+        fn delete_texture(&mut self, texture: Self::TextureId) {
+            self.log.push(format!("delete_texture {:?}", texture));
+            self.textures.remove(&texture);
         }
     }
 
@@ -670,8 +2352,48 @@ mod tests {
             self.push(format!("push_rect {:?} {:?}", bounds, style));
         }
 
+        fn push_triangle(&mut self, a: Pos, b: Pos, c: Pos, color: Color) {
+            self.push(format!("push_triangle {:?} {:?} {:?} {:?}", a, b, c, color));
+        }
+
         fn push_layer(&mut self, layer: usize, origin: Pos) {
             self.push(format!("push_layer {:?} {:?}", layer, origin));
         }
+
+        fn push_filters(&mut self, filters: &[Filter]) {
+            self.push(format!("push_filters {:?}", filters));
+        }
+
+        fn pop_filters(&mut self) {
+            self.push("pop_filters".to_string());
+        }
+
+        fn push_snapshot(&mut self, bounds: Bounds, texture: usize) {
+            self.push(format!("push_snapshot {:?} {:?}", bounds, texture));
+        }
+
+        fn push_blend_mode(&mut self, mode: BlendMode) {
+            self.push(format!("push_blend_mode {:?}", mode));
+        }
+
+        fn pop_blend_mode(&mut self) {
+            self.push("pop_blend_mode".to_string());
+        }
+
+        fn push_clip_path(&mut self, path: &[PathCommand]) {
+            self.push(format!("push_clip_path {:?}", path));
+        }
+
+        fn pop_clip_path(&mut self) {
+            self.push("pop_clip_path".to_string());
+        }
+
+        fn push_mask(&mut self, bounds: Bounds, texture: usize) {
+            self.push(format!("push_mask {:?} {:?}", bounds, texture));
+        }
+
+        fn pop_mask(&mut self) {
+            self.push("pop_mask".to_string());
+        }
     }
 }
@@ -0,0 +1,271 @@
+// animated image playback
+// - frames are composited into the canvas (which is then uploaded to the texture)
+// - time is not measured here, it's whatever was passed to `advance()`
+
+use super::decoding::{blit, AnimatedImage, Blend, Disposal};
+use std::time::Duration;
+
+// browsers do the same thing for (broken) gifs with no delay
+const MIN_DELAY: Duration = Duration::from_millis(10);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+pub struct Animation {
+    image: AnimatedImage,
+    canvas: Box<[u8]>,
+    // canvas before the current frame was drawn (for `Disposal::Previous`)
+    prev_canvas: Option<Box<[u8]>>,
+    current: usize,
+    plays: u32,
+    // None until the first `advance()`
+    next_frame_at: Option<Duration>,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(image: AnimatedImage) -> Self {
+        let canvas = vec![0; (image.width * image.height * 4) as usize].into_boxed_slice();
+
+        let mut anim = Self {
+            image,
+            canvas,
+            prev_canvas: None,
+            current: 0,
+            plays: 0,
+            next_frame_at: None,
+            finished: false,
+        };
+
+        anim.draw_frame(0);
+
+        anim
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.image.width, self.image.height)
+    }
+
+    pub fn canvas(&self) -> &[u8] {
+        &self.canvas
+    }
+
+    // returns true if the canvas has changed
+    pub fn advance(&mut self, now: Duration) -> bool {
+        if self.finished || self.image.frames.len() < 2 {
+            return false;
+        }
+
+        // first call starts the clock
+        let mut next = match self.next_frame_at {
+            Some(next) => next,
+            None => {
+                self.next_frame_at = Some(now + self.delay(self.current));
+                return false;
+            }
+        };
+
+        if now < next {
+            return false;
+        }
+
+        // skip whole loops if we are too much behind (app was suspended, etc.)
+        let total: Duration = (0..self.image.frames.len()).map(|i| self.delay(i)).sum();
+
+        if self.image.plays.is_none() && now - next > total {
+            next += total * ((now - next).as_nanos() / total.as_nanos()) as u32;
+        }
+
+        while now >= next {
+            if self.current == self.image.frames.len() - 1 {
+                self.plays += 1;
+
+                if Some(self.plays) == self.image.plays {
+                    self.finished = true;
+                    break;
+                }
+            }
+
+            self.dispose_frame(self.current);
+            self.current = (self.current + 1) % self.image.frames.len();
+
+            // every loop starts with empty canvas
+            if self.current == 0 {
+                self.canvas.iter_mut().for_each(|b| *b = 0);
+            }
+
+            self.draw_frame(self.current);
+            next += self.delay(self.current);
+        }
+
+        self.next_frame_at = Some(next);
+
+        true
+    }
+
+    fn delay(&self, frame: usize) -> Duration {
+        match self.image.frames[frame].delay {
+            d if d < MIN_DELAY => DEFAULT_DELAY,
+            d => d,
+        }
+    }
+
+    fn draw_frame(&mut self, frame: usize) {
+        let width = self.image.width;
+        let frame = &self.image.frames[frame];
+
+        // nothing to draw (and nothing to dispose later)
+        if frame.width <= 0 || frame.height <= 0 {
+            return;
+        }
+
+        if frame.disposal == Disposal::Previous {
+            self.prev_canvas = Some(self.canvas.clone());
+        }
+
+        match frame.blend {
+            Blend::Source => blit(&mut self.canvas, width, frame.x, frame.y, frame.width, &frame.data),
+            Blend::Over => {
+                for (i, src) in frame.data.chunks_exact(4).enumerate() {
+                    let (x, y) = (frame.x + i as i32 % frame.width, frame.y + i as i32 / frame.width);
+
+                    if x < width && y < self.image.height {
+                        let start = ((y * width + x) * 4) as usize;
+                        blend_over(&mut self.canvas[start..start + 4], src);
+                    }
+                }
+            }
+        }
+    }
+
+    fn dispose_frame(&mut self, frame: usize) {
+        let frame = &self.image.frames[frame];
+
+        if frame.width <= 0 || frame.height <= 0 {
+            return;
+        }
+
+        match frame.disposal {
+            Disposal::None => {}
+            Disposal::Background => {
+                let empty = vec![0; (frame.width * frame.height * 4) as usize];
+                blit(&mut self.canvas, self.image.width, frame.x, frame.y, frame.width, &empty);
+            }
+            Disposal::Previous => {
+                if let Some(prev) = self.prev_canvas.take() {
+                    self.canvas = prev;
+                }
+            }
+        }
+    }
+}
+
+// non-premultiplied `src` over `dest`
+fn blend_over(dest: &mut [u8], src: &[u8]) {
+    let (sa, da) = (src[3] as u32, dest[3] as u32);
+    let a = sa + da * (255 - sa) / 255;
+
+    if a == 0 {
+        return dest.copy_from_slice(&[0, 0, 0, 0]);
+    }
+
+    for i in 0..3 {
+        dest[i] = ((src[i] as u32 * sa + dest[i] as u32 * da * (255 - sa) / 255) / a) as u8;
+    }
+
+    dest[3] = a as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decoding::Frame;
+    use super::*;
+
+    #[test]
+    fn playback() {
+        let mut anim = Animation::new(AnimatedImage {
+            width: 2,
+            height: 1,
+            frames: vec![frame(0, [1, 1, 1, 255], 100, Disposal::None), frame(1, [2, 2, 2, 255], 50, Disposal::None)],
+            plays: Some(2),
+        });
+
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255, 0, 0, 0, 0]);
+
+        // start
+        assert!(!anim.advance(ms(1000)));
+        assert!(!anim.advance(ms(1099)));
+
+        assert!(anim.advance(ms(1100)));
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255, 2, 2, 2, 255]);
+
+        // canvas is cleared before the next loop
+        assert!(anim.advance(ms(1150)));
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255, 0, 0, 0, 0]);
+
+        // 2 plays, last frame stays
+        assert!(anim.advance(ms(2000)));
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255, 2, 2, 2, 255]);
+        assert!(!anim.advance(ms(3000)));
+    }
+
+    #[test]
+    fn disposal() {
+        let mut anim = Animation::new(AnimatedImage {
+            width: 2,
+            height: 1,
+            frames: vec![
+                frame(0, [1, 1, 1, 255], 10, Disposal::Background),
+                frame(1, [2, 2, 2, 255], 10, Disposal::Previous),
+                frame(0, [3, 3, 3, 128], 10, Disposal::None),
+            ],
+            plays: None,
+        });
+
+        anim.advance(ms(0));
+
+        anim.advance(ms(10));
+        assert_eq!(anim.canvas(), &[0, 0, 0, 0, 2, 2, 2, 255]);
+
+        // previous is restored and then blended over
+        anim.advance(ms(20));
+        assert_eq!(anim.canvas(), &[3, 3, 3, 128, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn empty_frames() {
+        let empty = Frame {
+            width: 0,
+            data: Box::new([]),
+            ..frame(0, [0; 4], 10, Disposal::Background)
+        };
+        let mut anim = Animation::new(AnimatedImage {
+            width: 1,
+            height: 1,
+            frames: vec![frame(0, [1, 1, 1, 255], 10, Disposal::None), empty],
+            plays: None,
+        });
+
+        anim.advance(ms(0));
+        assert!(anim.advance(ms(10)));
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255]);
+
+        assert!(anim.advance(ms(20)));
+        assert_eq!(anim.canvas(), &[1, 1, 1, 255]);
+    }
+
+    fn frame(x: i32, px: [u8; 4], delay: u64, disposal: Disposal) -> Frame {
+        Frame {
+            x,
+            y: 0,
+            width: 1,
+            height: 1,
+            data: Box::new(px),
+            delay: ms(delay),
+            disposal,
+            blend: Blend::Over,
+        }
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+}
@@ -21,7 +21,7 @@
 //
 //   x border
 //     x solid
-//     - triangle (half of the edge is transparent)
+//     x triangle (half of the edge is transparent)
 //       - maybe (solid only) push_triangle but supporting arbitrary fill styles could
 //         be very challenging for other backends
 //         - raqote can't do perspective 3D transform needed for 3 different uv coords.
@@ -30,17 +30,22 @@
 //       - gen image/msdf texture elsewhere
 //         (for uniform edges, one should be fine for each style)
 //
-//   - filter, backdrop-filter (postprocess)
+//   x filter (postprocess, push/pop offscreen group)
+//     x opacity is applied as the last `Filter::Opacity`
+//   x backdrop-filter (snapshot of what's already drawn, filtered & drawn back)
+//   x mix-blend-mode (push/pop offscreen group)
+//   x clip-path (push/pop path clip), mask (push/pop offscreen group)
 //
 // - it should be fast to change text color
 //   - not sure yet, maybe shared uniform for color multiplying
 //     (and opacity could be just special-case of that)
 
-use super::Color;
+use super::{BlendMode, Color, Filter, ImageRendering, PathCommand, RenderError};
 use crate::commons::{Bounds, Pos};
 
-// ref impl.
-// TODO: maybe it could be run with `cargo test --features=raqote`
+// ref impl. (pixels are checked against goldens, see raqote/golden.rs)
+// optional, `cargo test --no-default-features` runs without any rendering deps
+#[cfg(feature = "raqote")]
 pub mod raqote;
 
 // - can fill rects/triangles using specific graphics API like OpenGL
@@ -53,7 +58,8 @@ pub mod raqote;
 pub trait RenderBackend: Sized {
     // impl-specific handles
     type LayerId: Copy;
-    type TextureId: Copy;
+    // (comparable, so that rects can be batched by texture)
+    type TextureId: Copy + PartialEq;
 
     // impl-specific layer-state builder
     type LayerBuilder: LayerBuilder<Self>;
@@ -66,21 +72,60 @@ pub trait RenderBackend: Sized {
     fn rebuild_layer_with(&mut self, layer: Self::LayerId, f: impl FnMut(&mut Self::LayerBuilder));
 
     // actually draw something
-    fn render_layer(&mut self, layer: Self::LayerId);
+    // (only damaged rects need to be cleared & redrawn, the rest is kept from the last time)
+    fn render_layer(&mut self, layer: Self::LayerId, damage: &[Bounds]) -> Result<(), RenderError>;
 
     // so there's no copying
-    fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Self::TextureId;
+    // (RGBA, data which doesn't match the size is an error)
+    fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Result<Self::TextureId, RenderError>;
 
     // needed for atlasing
     fn update_texture(&mut self, texture: Self::TextureId, f: impl FnMut(&mut [u8]));
+
+    // texture for `LayerBuilder::push_snapshot()`, empty until the layer is rendered
+    // (and then overwritten every time)
+    fn create_snapshot_texture(&mut self) -> Result<Self::TextureId, RenderError>;
+
+    // texture is not going to be used anymore (and the handle might be reused)
+    fn delete_texture(&mut self, texture: Self::TextureId);
 }
 
 pub trait LayerBuilder<RB: RenderBackend> {
-    // TODO: push/pop transform/clip/opacity
+    // TODO: push/pop transform
 
     fn push_rect(&mut self, bounds: Bounds, style: FillStyle<RB>);
 
+    // solid only, see the notes at the top
+    fn push_triangle(&mut self, a: Pos, b: Pos, c: Pos, color: Color);
+
     fn push_layer(&mut self, layer: RB::LayerId, origin: Pos);
+
+    // everything until the matching `pop_filters()` should be rendered
+    // offscreen & filtered before it's composited back
+    fn push_filters(&mut self, filters: &[Filter]);
+
+    fn pop_filters(&mut self);
+
+    // what's already drawn under the bounds is copied to the snapshot texture (stretched over the bounds)
+    // & cleared inside of the current clip, so that it can be replaced with a filtered copy (backdrop-filter)
+    // (pixels outside of the target are extended from its edges)
+    fn push_snapshot(&mut self, bounds: Bounds, texture: RB::TextureId);
+
+    // everything until the matching `pop_blend_mode()` is composited using the mode
+    fn push_blend_mode(&mut self, mode: BlendMode);
+
+    fn pop_blend_mode(&mut self);
+
+    // everything until the matching `pop_clip_path()` is clipped by the (closed) path
+    fn push_clip_path(&mut self, path: &[PathCommand]);
+
+    fn pop_clip_path(&mut self);
+
+    // everything until the matching `pop_mask()` is multiplied by the alpha
+    // of the texture stretched over the bounds (and transparent outside)
+    fn push_mask(&mut self, bounds: Bounds, texture: RB::TextureId);
+
+    fn pop_mask(&mut self);
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -91,7 +136,8 @@ pub enum FillStyle<RB: RenderBackend> {
     SolidColor(Color),
 
     // images, gradients, shadows, ...
-    Texture(RB::TextureId, Bounds),
+    // (uv outside of 0..1 means the texture should repeat)
+    Texture(RB::TextureId, Bounds, ImageRendering),
 
     // text, radii corners, maybe even paths & preprocessed SVG (later)
     Msdf {
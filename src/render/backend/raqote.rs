@@ -1,26 +1,82 @@
-use super::{Color, FillStyle, LayerBuilder, RenderBackend};
+use super::{BlendMode, Color, FillStyle, Filter, ImageRendering, LayerBuilder, PathCommand, RenderBackend, RenderError};
 use crate::commons::{Bounds, Pos};
+use crate::render::error::check_image_data;
 use raqote::*;
+use std::cell::{OnceCell, RefCell};
+use std::fs::File;
+use std::io::{self, BufWriter};
 
-// temporary backend just to test the renderer works properly
-// might be a thing in future but now it just writes PNG file
+mod filters;
+
+#[cfg(test)]
+mod golden;
+
+// CPU backend, renders into an owned RGBA buffer
+// (reference for other backends, tests, thumbnails, server-side rendering)
 
 pub struct RaqoteBackend {
-    out_file: String,
     dt: DrawTarget,
+    // non-premultiplied RGBA, updated after each render
+    pixels: Vec<u8>,
+    clear_color: Color,
+    // whole target has to be cleared (first render, new clear color)
+    clear_all: bool,
     layers: Vec<Vec<RenderOp>>,
     textures: Vec<Texture>,
 }
 
 impl RaqoteBackend {
-    pub fn new(out_file: String, width: i32, height: i32) -> Self {
+    pub fn new(width: i32, height: i32) -> Self {
         Self {
-            out_file,
             dt: DrawTarget::new(width, height),
+            pixels: vec![0; (width * height * 4) as usize],
+            clear_color: Color::TRANSPARENT,
+            clear_all: true,
             layers: Vec::new(),
             textures: Vec::new(),
         }
     }
+
+    pub fn width(&self) -> i32 {
+        self.dt.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.dt.height()
+    }
+
+    // non-premultiplied RGBA, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    // damaged regions are cleared with this before they are redrawn
+    // (everything is, during the first render & after it's changed)
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_all |= self.clear_color != color;
+        self.clear_color = color;
+    }
+
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        write_png(path, self.width(), self.height(), &self.pixels)
+    }
+
+    // copy (and convert) damaged pixels to the RGBA buffer
+    fn sync_pixels(&mut self, (x0, y0, x1, y1): (i32, i32, i32, i32)) {
+        let width = self.width();
+        let data = self.dt.get_data();
+
+        for y in y0.max(0)..y1.min(self.height()) {
+            for x in x0.max(0)..x1.min(width) {
+                let i = (y * width + x) as usize;
+                let px = data[i];
+                let a = px >> 24;
+                let unpremul = |c: u32| ((c & 0xFF) * 255 + a / 2).checked_div(a).unwrap_or(0) as u8;
+
+                self.pixels[i * 4..i * 4 + 4].copy_from_slice(&[unpremul(px >> 16), unpremul(px >> 8), unpremul(px), a as u8]);
+            }
+        }
+    }
 }
 
 impl RenderBackend for RaqoteBackend {
@@ -35,43 +91,292 @@ impl RenderBackend for RaqoteBackend {
     }
 
     fn rebuild_layer_with(&mut self, layer: Self::LayerId, mut f: impl FnMut(&mut Self::LayerBuilder)) {
+        self.layers[layer].clear();
         f(&mut self.layers[layer]);
     }
 
-    fn render_layer(&mut self, layer: Self::LayerId) {
-        //self.dt.clear(Color::BLACK.into());
+    fn render_layer(&mut self, layer: Self::LayerId, damage: &[Bounds]) -> Result<(), RenderError> {
+        if layer >= self.layers.len() {
+            return Err(RenderError::Backend(format!("unknown layer {}", layer)));
+        }
+
+        // (even what's outside of the root)
+        let whole = [Bounds {
+            a: Pos::ZERO,
+            b: Pos {
+                x: self.width() as f32,
+                y: self.height() as f32,
+            },
+        }];
+        let damage = if std::mem::take(&mut self.clear_all) { &whole } else { damage };
+
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        // whole pixels
+        let rects: Vec<_> = damage
+            .iter()
+            .map(|Bounds { a, b }| (a.x.floor() as i32, a.y.floor() as i32, b.x.ceil() as i32, b.y.ceil() as i32))
+            .collect();
 
-        render_op(&RenderOp::Layer(layer, Pos::ZERO), &self.layers, &self.textures, &mut self.dt);
+        let clear = DrawOptions {
+            blend_mode: raqote::BlendMode::Src,
+            ..DrawOptions::new()
+        };
+
+        // one by one (overlapping pixels are cleared & drawn again)
+        for &(x0, y0, x1, y1) in &rects {
+            self.dt.push_clip_rect(IntRect::new(IntPoint::new(x0, y0), IntPoint::new(x1, y1)));
+            self.dt
+                .fill_rect(x0 as f32, y0 as f32, (x1 - x0) as f32, (y1 - y0) as f32, &solid(self.clear_color), &clear);
+            render_ops(&self.layers[layer], &self.layers, &mut self.textures, &mut self.dt);
+            self.dt.pop_clip();
+        }
 
-        // TODO: render
-        //let _data = self.dt.get_data();
+        for r in rects {
+            self.sync_pixels(r);
+        }
 
-        self.dt.write_png(&self.out_file).unwrap();
+        Ok(())
     }
 
-    fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Self::TextureId {
-        assert_eq!(data.len() as i32, width * height * 4, "invalid texture data len");
-        self.textures.push(Texture { width, height, data });
+    fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Result<Self::TextureId, RenderError> {
+        check_image_data(width, height, &data)?;
+        self.textures.push(Texture::new(width, height, data));
 
-        self.textures.len() - 1
+        Ok(self.textures.len() - 1)
     }
 
     fn update_texture(&mut self, texture: Self::TextureId, mut f: impl FnMut(&mut [u8])) {
-        f(&mut self.textures[texture].data);
+        let texture = &mut self.textures[texture];
+
+        f(&mut texture.data);
+        texture.pixels = to_premultiplied_argb(&texture.data);
+        texture.mips = OnceCell::new();
+        texture.resampled = RefCell::new(None);
+    }
+
+    fn create_snapshot_texture(&mut self) -> Result<Self::TextureId, RenderError> {
+        self.textures.push(Texture::new(0, 0, Box::new([])));
+
+        Ok(self.textures.len() - 1)
+    }
+
+    fn delete_texture(&mut self, texture: Self::TextureId) {
+        // TODO: freelist
+        self.textures[texture] = Texture::new(0, 0, Box::new([]));
     }
 }
 
+// non-premultiplied RGBA
+fn write_png(path: impl AsRef<std::path::Path>, width: i32, height: i32, data: &[u8]) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(data)?;
+
+    Ok(())
+}
+
 impl LayerBuilder<RaqoteBackend> for Vec<RenderOp> {
     fn push_rect(&mut self, bounds: Bounds, style: FillStyle<RaqoteBackend>) {
         self.push(RenderOp::FillRect(bounds, style));
     }
 
+    fn push_triangle(&mut self, a: Pos, b: Pos, c: Pos, color: Color) {
+        self.push(RenderOp::FillTriangle([a, b, c], color));
+    }
+
     fn push_layer(&mut self, layer: <RaqoteBackend as RenderBackend>::LayerId, origin: Pos) {
         self.push(RenderOp::Layer(layer, origin));
     }
+
+    fn push_filters(&mut self, filters: &[Filter]) {
+        self.push(RenderOp::PushFilters(filters.to_vec()));
+    }
+
+    fn pop_filters(&mut self) {
+        self.push(RenderOp::PopFilters);
+    }
+
+    fn push_blend_mode(&mut self, mode: BlendMode) {
+        self.push(RenderOp::PushBlendMode(mode));
+    }
+
+    fn pop_blend_mode(&mut self) {
+        self.push(RenderOp::PopBlendMode);
+    }
+
+    fn push_snapshot(&mut self, bounds: Bounds, texture: <RaqoteBackend as RenderBackend>::TextureId) {
+        self.push(RenderOp::Snapshot(bounds, texture));
+    }
+
+    fn push_clip_path(&mut self, path: &[PathCommand]) {
+        self.push(RenderOp::PushClipPath(path.to_vec()));
+    }
+
+    fn pop_clip_path(&mut self) {
+        self.push(RenderOp::PopClipPath);
+    }
+
+    fn push_mask(&mut self, bounds: Bounds, texture: <RaqoteBackend as RenderBackend>::TextureId) {
+        self.push(RenderOp::PushMask(bounds, texture));
+    }
+
+    fn pop_mask(&mut self) {
+        self.push(RenderOp::PopMask);
+    }
+}
+
+// groups (push/pop) are rendered into offscreen targets
+fn render_ops(ops: &[RenderOp], layers: &[Vec<RenderOp>], textures: &mut [Texture], dt: &mut DrawTarget) {
+    let mut stack: Vec<(DrawTarget, Group)> = Vec::new();
+
+    for op in ops {
+        let target = match stack.last_mut() {
+            Some((target, _)) => target,
+            None => &mut *dt,
+        };
+
+        match op {
+            RenderOp::PushFilters(filters) => push_group(&mut stack, dt, Group::Filters(filters)),
+            RenderOp::PushBlendMode(mode) => push_group(&mut stack, dt, Group::BlendMode(*mode)),
+            RenderOp::PushMask(bounds, texture) => push_group(&mut stack, dt, Group::Mask(*bounds, *texture)),
+
+            // groups are always nested properly
+            RenderOp::PopFilters | RenderOp::PopBlendMode | RenderOp::PopMask => pop_group(&mut stack, textures, dt),
+
+            // clips are kept by the target (in device space)
+            RenderOp::PushClipPath(commands) => target.push_clip(&to_path(commands)),
+            RenderOp::PopClipPath => target.pop_clip(),
+
+            RenderOp::Snapshot(bounds, texture) => {
+                textures[*texture] = Texture::snapshot(target, *bounds);
+
+                let path = {
+                    let mut pb = PathBuilder::new();
+                    pb.rect(bounds.a.x, bounds.a.y, bounds.width(), bounds.height());
+                    pb.finish()
+                };
+                let clear = DrawOptions {
+                    blend_mode: raqote::BlendMode::Src,
+                    ..DrawOptions::new()
+                };
+
+                // (src is unbounded, it'd clear everything inside of the clip otherwise)
+                target.push_clip(&path);
+                target.fill(&path, &solid(Color::TRANSPARENT), &clear);
+                target.pop_clip();
+            }
+
+            _ => render_op(op, layers, textures, target),
+        }
+    }
+
+    // unbalanced push
+    while !stack.is_empty() {
+        pop_group(&mut stack, textures, dt);
+    }
+}
+
+enum Group<'a> {
+    Filters(&'a [Filter]),
+    BlendMode(BlendMode),
+    Mask(Bounds, <RaqoteBackend as RenderBackend>::TextureId),
+}
+
+fn push_group<'a>(stack: &mut Vec<(DrawTarget, Group<'a>)>, dt: &DrawTarget, group: Group<'a>) {
+    let target = stack.last().map(|(target, _)| target).unwrap_or(dt);
+    let mut offscreen = DrawTarget::new(target.width(), target.height());
+    offscreen.set_transform(target.get_transform());
+    stack.push((offscreen, group));
 }
 
-fn render_op(op: &RenderOp, layers: &[Vec<RenderOp>], textures: &[Texture], dt: &mut DrawTarget) {
+fn pop_group(stack: &mut Vec<(DrawTarget, Group)>, textures: &[Texture], dt: &mut DrawTarget) {
+    let (mut offscreen, group) = match stack.pop() {
+        Some(group) => group,
+        None => return,
+    };
+
+    let (width, height) = (offscreen.width(), offscreen.height());
+    let mut options = DrawOptions::new();
+
+    match group {
+        Group::Filters(filters) => {
+            for f in filters {
+                filters::apply(f, offscreen.get_data_mut(), width, height);
+            }
+        }
+        Group::BlendMode(mode) => options.blend_mode = mode.into(),
+        Group::Mask(bounds, texture) => apply_mask(&mut offscreen, bounds, &textures[texture]),
+    }
+
+    let target = match stack.last_mut() {
+        Some((target, _)) => target,
+        None => dt,
+    };
+
+    // offscreen is already in device space
+    let transform = *target.get_transform();
+    target.set_transform(&Transform::identity());
+    target.draw_image_at(
+        0.,
+        0.,
+        &Image {
+            width,
+            height,
+            data: offscreen.get_data(),
+        },
+        &options,
+    );
+    target.set_transform(&transform);
+}
+
+// multiply everything by the alpha of the texture stretched over the bounds
+fn apply_mask(offscreen: &mut DrawTarget, bounds: Bounds, texture: &Texture) {
+    let mut mask = DrawTarget::new(offscreen.width(), offscreen.height());
+    mask.set_transform(offscreen.get_transform());
+
+    if texture.width > 0 && texture.height > 0 {
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.rect(bounds.a.x, bounds.a.y, bounds.width(), bounds.height());
+            pb.finish()
+        };
+
+        let uv = Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 1., y: 1. },
+        };
+        fill_texture(&mut mask, &path, bounds, texture, uv, ImageRendering::Smooth);
+    }
+
+    for (px, m) in offscreen.get_data_mut().iter_mut().zip(mask.get_data()) {
+        let alpha = m >> 24;
+        let mul = |shift: u32| ((((*px >> shift) & 0xFF) * alpha + 127) / 255) << shift;
+        *px = mul(24) | mul(16) | mul(8) | mul(0);
+    }
+}
+
+fn to_path(commands: &[PathCommand]) -> Path {
+    let mut pb = PathBuilder::new();
+
+    for c in commands {
+        match *c {
+            PathCommand::MoveTo(p) => pb.move_to(p.x, p.y),
+            PathCommand::LineTo(p) => pb.line_to(p.x, p.y),
+            PathCommand::QuadTo(c, p) => pb.quad_to(c.x, c.y, p.x, p.y),
+            PathCommand::CubicTo(c1, c2, p) => pb.cubic_to(c1.x, c1.y, c2.x, c2.y, p.x, p.y),
+            PathCommand::Close => pb.close(),
+        }
+    }
+
+    pb.finish()
+}
+
+fn render_op(op: &RenderOp, layers: &[Vec<RenderOp>], textures: &mut [Texture], dt: &mut DrawTarget) {
     match op {
         RenderOp::FillRect(bounds, style) => {
             let path = {
@@ -81,25 +386,21 @@ fn render_op(op: &RenderOp, layers: &[Vec<RenderOp>], textures: &[Texture], dt:
                 pb.finish()
             };
 
-            // fill style
-            let source = match style {
-                FillStyle::SolidColor(color) => Source::Solid((*color).into()),
-
-                FillStyle::Texture(texture, uv) => {
-                    let Texture { width, height, ref data } = textures[*texture];
-                    let data = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u32, data.len() / 4) };
-
-                    let (w, h) = (width as f32, height as f32);
-                    let transform = Transform::create_translation((uv.a.x * w) - bounds.a.x, (uv.a.y * h) - bounds.a.y)
-                        .post_scale((uv.width() * w) / bounds.width(), (uv.height() * h) / bounds.height());
-
-                    Source::Image(Image { width, height, data }, ExtendMode::Pad, FilterMode::Nearest, transform)
-                }
+            match style {
+                FillStyle::SolidColor(color) => dt.fill(&path, &solid(*color), &DrawOptions::new()),
+                FillStyle::Texture(texture, uv, rendering) => fill_texture(dt, &path, *bounds, &textures[*texture], *uv, *rendering),
+                FillStyle::Msdf { texture, uv, factor, color } => fill_msdf(dt, &path, *bounds, &textures[*texture], *uv, *factor, *color),
+            }
+        }
 
-                FillStyle::Msdf { .. } => panic!("TODO: msdf"),
-            };
+        RenderOp::FillTriangle([a, b, c], color) => {
+            let mut pb = PathBuilder::new();
+            pb.move_to(a.x, a.y);
+            pb.line_to(b.x, b.y);
+            pb.line_to(c.x, c.y);
+            pb.close();
 
-            dt.fill(&path, &source, &DrawOptions::new());
+            dt.fill(&pb.finish(), &solid(*color), &DrawOptions::new());
         }
 
         RenderOp::Layer(id, origin) => {
@@ -107,38 +408,631 @@ fn render_op(op: &RenderOp, layers: &[Vec<RenderOp>], textures: &[Texture], dt:
 
             dt.set_transform(&prev_transform.post_translate(euclid::vec2(origin.x, origin.y)));
 
-            for op in &layers[*id] {
-                render_op(op, layers, textures, dt);
-            }
+            render_ops(&layers[*id], layers, textures, dt);
 
             dt.set_transform(&prev_transform);
         }
+
+        // handled in `render_ops()`
+        RenderOp::PushFilters(_)
+        | RenderOp::PopFilters
+        | RenderOp::PushBlendMode(_)
+        | RenderOp::PopBlendMode
+        | RenderOp::PushClipPath(_)
+        | RenderOp::PopClipPath
+        | RenderOp::PushMask(..)
+        | RenderOp::PopMask
+        | RenderOp::Snapshot(..) => {}
+    }
+}
+
+fn fill_texture(dt: &mut DrawTarget, path: &Path, bounds: Bounds, texture: &Texture, uv: Bounds, rendering: ImageRendering) {
+    // (deleted, or a snapshot of nothing)
+    if texture.width == 0 || texture.height == 0 {
+        return;
+    }
+
+    // texels per pixel
+    let scale_x = (uv.width() * texture.width as f32 / bounds.width()).abs();
+    let scale_y = (uv.height() * texture.height as f32 / bounds.height()).abs();
+
+    // plain bilinear/nearest, unless asked for prefiltering
+    if rendering != ImageRendering::HighQuality || (scale_x <= 1. && scale_y <= 1.) {
+        return fill_image(dt, path, bounds, (texture.width, texture.height, &texture.pixels), uv, rendering);
+    }
+
+    // pick mip level (so that it's still bigger than the dest)
+    let level = scale_x.max(scale_y).log2() as usize;
+    let (width, height, pixels) = texture.mip(level);
+
+    // and scale it down the rest of the way
+    let (scale_x, scale_y) = (scale_x * width as f32 / texture.width as f32, scale_y * height as f32 / texture.height as f32);
+    let (w, h) = ((width as f32 / scale_x.max(1.)).ceil() as i32, (height as f32 / scale_y.max(1.)).ceil() as i32);
+
+    if (w, h) == (width, height) {
+        return fill_image(dt, path, bounds, (width, height, pixels), uv, rendering);
     }
+
+    let mut resampled = texture.resampled.borrow_mut();
+    let Mip { pixels, .. } = match &mut *resampled {
+        Some((l, mip)) if *l == level && (mip.width, mip.height) == (w, h) => mip,
+        slot => {
+            let pixels = resample(pixels, width, height, w, h);
+            &slot.insert((level, Mip { width: w, height: h, pixels })).1
+        }
+    };
+
+    fill_image(dt, path, bounds, (w, h, pixels), uv, rendering);
+}
+
+fn fill_image(dt: &mut DrawTarget, path: &Path, bounds: Bounds, (width, height, pixels): (i32, i32, &[u32]), uv: Bounds, rendering: ImageRendering) {
+    let filter = match rendering {
+        ImageRendering::Pixelated => FilterMode::Nearest,
+        _ => FilterMode::Bilinear,
+    };
+
+    let (w, h) = (width as f32, height as f32);
+    let transform = Transform::create_translation(-bounds.a.x, -bounds.a.y)
+        .post_scale((uv.width() * w) / bounds.width(), (uv.height() * h) / bounds.height())
+        .post_translate(euclid::vec2(uv.a.x * w, uv.a.y * h));
+
+    // uv outside of 0..1 means repeat
+    let extend = match uv {
+        Bounds { a, b } if a.x < 0. || a.y < 0. || b.x > 1. || b.y > 1. => ExtendMode::Repeat,
+        _ => ExtendMode::Pad,
+    };
+
+    let source = Source::Image(Image { width, height, data: pixels }, extend, filter, transform);
+
+    dt.fill(path, &source, &DrawOptions::new());
+}
+
+// rasterized on cpu, into bounds-sized image
+fn fill_msdf(dt: &mut DrawTarget, path: &Path, bounds: Bounds, texture: &Texture, uv: Bounds, factor: f32, color: Color) {
+    let (width, height) = (bounds.width().ceil() as i32, bounds.height().ceil() as i32);
+
+    if width <= 0 || height <= 0 || texture.width == 0 || texture.height == 0 {
+        return;
+    }
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = uv.a.x + (x as f32 + 0.5) / bounds.width() * uv.width();
+            let v = uv.a.y + (y as f32 + 0.5) / bounds.height() * uv.height();
+            let [r, g, b] = texture.sample(u, v);
+
+            // median of the 3 channels is the distance
+            let sd = r.max(g).min(r.min(g).max(b));
+            let alpha = ((sd - 0.5) * factor + 0.5).clamp(0., 1.) * color.a as f32 / 255.;
+
+            let premul = |c: u8| (c as f32 * alpha + 0.5) as u32;
+            pixels.push((((alpha * 255. + 0.5) as u32) << 24) | (premul(color.r) << 16) | (premul(color.g) << 8) | premul(color.b));
+        }
+    }
+
+    let transform = Transform::create_translation(-bounds.a.x, -bounds.a.y);
+    let source = Source::Image(Image { width, height, data: &pixels }, ExtendMode::Pad, FilterMode::Nearest, transform);
+
+    dt.fill(path, &source, &DrawOptions::new());
 }
 
 pub enum RenderOp {
     FillRect(Bounds, FillStyle<RaqoteBackend>),
+    FillTriangle([Pos; 3], Color),
     Layer(<RaqoteBackend as RenderBackend>::LayerId, Pos),
-}
-
-pub enum Shape {
-    Triangle(Pos, Pos, Pos),
-    Rect(Bounds),
+    PushFilters(Vec<Filter>),
+    PopFilters,
+    Snapshot(Bounds, <RaqoteBackend as RenderBackend>::TextureId),
+    PushBlendMode(BlendMode),
+    PopBlendMode,
+    PushClipPath(Vec<PathCommand>),
+    PopClipPath,
+    PushMask(Bounds, <RaqoteBackend as RenderBackend>::TextureId),
+    PopMask,
 }
 
 pub struct Texture {
     width: i32,
     height: i32,
+    // rgba, as it was given
     data: Box<[u8]>,
+    // what raqote wants
+    pixels: Box<[u32]>,
+    // half-sized levels, generated on the first downscaled high-quality draw
+    // (and thrown away when the texture is updated)
+    mips: OnceCell<Vec<Mip>>,
+    // last mip level resampled to the exact drawn size, so it's not redone
+    // for every frame & damage rect (and thrown away too)
+    resampled: RefCell<Option<(usize, Mip)>>,
 }
 
-impl Into<SolidSource> for Color {
-    fn into(self) -> SolidSource {
-        SolidSource {
-            r: self.r,
-            g: self.g,
-            b: self.b,
-            a: self.a,
+struct Mip {
+    width: i32,
+    height: i32,
+    pixels: Vec<u32>,
+}
+
+impl Texture {
+    fn new(width: i32, height: i32, data: Box<[u8]>) -> Self {
+        let pixels = to_premultiplied_argb(&data);
+
+        Self {
+            width,
+            height,
+            data,
+            pixels,
+            mips: OnceCell::new(),
+            resampled: RefCell::new(None),
         }
     }
+
+    // bilinear rgb sample (0..1) from the original data
+    fn sample(&self, u: f32, v: f32) -> [f32; 3] {
+        let (x, y) = (u * self.width as f32 - 0.5, v * self.height as f32 - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let px = |x: f32, y: f32, ch: usize| {
+            let x = (x as i32).max(0).min(self.width - 1);
+            let y = (y as i32).max(0).min(self.height - 1);
+            self.data[((y * self.width + x) * 4) as usize + ch] as f32 / 255.
+        };
+
+        let mut res = [0.; 3];
+
+        for (ch, res) in res.iter_mut().enumerate() {
+            let top = px(x0, y0, ch) * (1. - fx) + px(x0 + 1., y0, ch) * fx;
+            let bottom = px(x0, y0 + 1., ch) * (1. - fx) + px(x0 + 1., y0 + 1., ch) * fx;
+            *res = top * (1. - fy) + bottom * fy;
+        }
+
+        res
+    }
+
+    // region of what's been drawn so far (to whole pixels, the edges are extended)
+    fn snapshot(dt: &DrawTarget, bounds: Bounds) -> Self {
+        let transform = dt.get_transform();
+        let a = transform.transform_point(euclid::point2(bounds.a.x, bounds.a.y));
+        let b = transform.transform_point(euclid::point2(bounds.b.x, bounds.b.y));
+        let (x, y) = (a.x.round() as i32, a.y.round() as i32);
+        let (width, height) = (((b.x - a.x).round() as i32).max(0), ((b.y - a.y).round() as i32).max(0));
+
+        let data = dt.get_data();
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        if dt.width() > 0 && dt.height() > 0 {
+            for row in y..y + height {
+                let row = row.clamp(0, dt.height() - 1) * dt.width();
+                pixels.extend((x..x + width).map(|col| data[(row + col.clamp(0, dt.width() - 1)) as usize]));
+            }
+        }
+
+        // (empty target)
+        pixels.resize((width * height) as usize, 0);
+
+        Self {
+            width,
+            height,
+            // not needed (yet)
+            data: Box::new([]),
+            pixels: pixels.into_boxed_slice(),
+            mips: OnceCell::new(),
+            resampled: RefCell::new(None),
+        }
+    }
+
+    // 0 is the texture itself
+    fn mip(&self, level: usize) -> (i32, i32, &[u32]) {
+        if level == 0 {
+            return (self.width, self.height, &self.pixels);
+        }
+
+        let mips = self.mips.get_or_init(|| {
+            let mut mips = Vec::<Mip>::new();
+            let (mut width, mut height) = (self.width, self.height);
+
+            while width > 1 || height > 1 {
+                let prev = mips.last().map(|m| &m.pixels[..]).unwrap_or(&self.pixels);
+                let next = half(prev, width, height);
+
+                width = (width / 2).max(1);
+                height = (height / 2).max(1);
+                mips.push(Mip { width, height, pixels: next });
+            }
+
+            mips
+        });
+
+        match mips.get(level - 1).or_else(|| mips.last()) {
+            Some(Mip { width, height, pixels }) => (*width, *height, pixels),
+            None => (self.width, self.height, &self.pixels),
+        }
+    }
+}
+
+// next mip level (2x2 box filter)
+fn half(pixels: &[u32], width: i32, height: i32) -> Vec<u32> {
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut res = Vec::with_capacity((new_width * new_height) as usize);
+
+    for y in 0..new_height {
+        let (y0, y1) = (y * 2, (y * 2 + 1).min(height - 1));
+
+        for x in 0..new_width {
+            let (x0, x1) = (x * 2, (x * 2 + 1).min(width - 1));
+            let px = |x, y| pixels[(y * width + x) as usize];
+            let avg = |i: u32| (((px(x0, y0) >> i) & 0xFF) + ((px(x1, y0) >> i) & 0xFF) + ((px(x0, y1) >> i) & 0xFF) + ((px(x1, y1) >> i) & 0xFF) + 2) / 4;
+
+            res.push((avg(24) << 24) | (avg(16) << 16) | (avg(8) << 8) | avg(0));
+        }
+    }
+
+    res
+}
+
+// area-averaging (box filter) resize, for downscaling
+fn resample(pixels: &[u32], width: i32, height: i32, new_width: i32, new_height: i32) -> Vec<u32> {
+    let (sx, sy) = (width as f32 / new_width as f32, height as f32 / new_height as f32);
+    let mut res = Vec::with_capacity((new_width * new_height) as usize);
+
+    for y in 0..new_height {
+        let (y0, y1) = ((y as f32 * sy) as i32, (((y + 1) as f32 * sy).ceil() as i32).min(height));
+
+        for x in 0..new_width {
+            let (x0, x1) = ((x as f32 * sx) as i32, (((x + 1) as f32 * sx).ceil() as i32).min(width));
+            let mut sum = [0; 4];
+
+            for yy in y0..y1 {
+                for xx in x0..x1 {
+                    let px = pixels[(yy * width + xx) as usize];
+
+                    for (i, ch) in sum.iter_mut().enumerate() {
+                        *ch += (px >> (i * 8)) & 0xFF;
+                    }
+                }
+            }
+
+            let n = ((x1 - x0) * (y1 - y0)).max(1) as u32;
+            res.push(sum.iter().enumerate().fold(0, |px, (i, ch)| px | ((ch / n) << (i * 8))));
+        }
+    }
+
+    res
+}
+
+fn to_premultiplied_argb(rgba: &[u8]) -> Box<[u32]> {
+    rgba.chunks_exact(4)
+        .map(|px| {
+            let a = px[3] as u32;
+            let premul = |c: u8| (c as u32 * a + 127) / 255;
+
+            (a << 24) | (premul(px[0]) << 16) | (premul(px[1]) << 8) | premul(px[2])
+        })
+        .collect()
+}
+
+impl From<BlendMode> for raqote::BlendMode {
+    fn from(mode: BlendMode) -> Self {
+        match mode {
+            BlendMode::Normal => Self::SrcOver,
+            BlendMode::Multiply => Self::Multiply,
+            BlendMode::Screen => Self::Screen,
+            BlendMode::Overlay => Self::Overlay,
+            BlendMode::Darken => Self::Darken,
+            BlendMode::Lighten => Self::Lighten,
+            BlendMode::ColorDodge => Self::ColorDodge,
+            BlendMode::ColorBurn => Self::ColorBurn,
+            BlendMode::HardLight => Self::HardLight,
+            BlendMode::SoftLight => Self::SoftLight,
+            BlendMode::Difference => Self::Difference,
+            BlendMode::Exclusion => Self::Exclusion,
+            BlendMode::Hue => Self::Hue,
+            BlendMode::Saturation => Self::Saturation,
+            BlendMode::Color => Self::Color,
+            BlendMode::Luminosity => Self::Luminosity,
+        }
+    }
+}
+
+// raqote wants premultiplied colors, ours are straight alpha
+fn solid(color: Color) -> Source<'static> {
+    Source::Solid(SolidSource::from_unpremultiplied_argb(color.a, color.r, color.g, color.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mips() {
+        let mut data = vec![0; 4 * 2 * 4];
+        data[..4].copy_from_slice(&[255, 255, 255, 255]);
+
+        let texture = Texture::new(4, 2, data.into_boxed_slice());
+
+        assert_eq!(texture.mip(1), (2, 1, &[0x40404040, 0][..]));
+        assert_eq!(texture.mip(2), (1, 1, &[0x20202020][..]));
+        assert_eq!(texture.mip(5), (1, 1, &[0x20202020][..]));
+    }
+
+    #[test]
+    fn downscaled_rendering() {
+        let texture = Texture::new(8, 8, vec![255; 8 * 8 * 4].into());
+        let bounds = Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 3., y: 3. },
+        };
+        let uv = Bounds { a: Pos::ZERO, b: Pos::ONE };
+        let path = {
+            let mut pb = PathBuilder::new();
+            pb.rect(0., 0., 3., 3.);
+            pb.finish()
+        };
+        let mut dt = DrawTarget::new(3, 3);
+
+        // smooth is just bilinear, without any mips
+        fill_texture(&mut dt, &path, bounds, &texture, uv, ImageRendering::Smooth);
+        assert!(texture.mips.get().is_none());
+        assert!(texture.resampled.borrow().is_none());
+
+        // high-quality is resampled from the closest mip, and only once
+        fill_texture(&mut dt, &path, bounds, &texture, uv, ImageRendering::HighQuality);
+        let ptr = match &*texture.resampled.borrow() {
+            Some((1, Mip { width: 3, height: 3, pixels })) => pixels.as_ptr(),
+            _ => panic!("not resampled"),
+        };
+        fill_texture(&mut dt, &path, bounds, &texture, uv, ImageRendering::HighQuality);
+        assert_eq!(texture.resampled.borrow().as_ref().unwrap().1.pixels.as_ptr(), ptr);
+        assert_eq!(dt.get_data(), &[0xFFFFFFFF; 9]);
+    }
+
+    #[test]
+    fn filters() {
+        let rect = |x, color| {
+            RenderOp::FillRect(
+                Bounds {
+                    a: Pos { x, y: 0. },
+                    b: Pos { x: x + 1., y: 1. },
+                },
+                FillStyle::SolidColor(color),
+            )
+        };
+
+        let ops = vec![
+            rect(0., Color::RED),
+            RenderOp::PushFilters(vec![Filter::Invert(1.), Filter::Brightness(0.5)]),
+            rect(1., Color::RED),
+            RenderOp::PushFilters(vec![Filter::Grayscale(1.)]),
+            rect(2., Color::GREEN),
+            RenderOp::PopFilters,
+            RenderOp::PopFilters,
+            rect(3., Color::BLUE),
+        ];
+
+        let mut dt = DrawTarget::new(4, 1);
+        render_ops(&ops, &[], &mut [], &mut dt);
+
+        // nested group is filtered twice
+        assert_eq!(dt.get_data(), &[0xFFFF0000, 0xFF008080, 0xFF252525, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn translucent_fill() {
+        let ops = vec![RenderOp::FillRect(
+            Bounds { a: Pos::ZERO, b: Pos::ONE },
+            FillStyle::SolidColor(Color { r: 255, g: 0, b: 0, a: 128 }),
+        )];
+
+        let mut dt = DrawTarget::new(1, 1);
+        render_ops(&ops, &[], &mut [], &mut dt);
+
+        // premultiplied, not 0x80FF0000 (which would be an invalid color)
+        assert_eq!(dt.get_data(), &[0x80800000]);
+    }
+
+    #[test]
+    fn blend_modes() {
+        let rect = |color| RenderOp::FillRect(Bounds { a: Pos::ZERO, b: Pos::ONE }, FillStyle::SolidColor(color));
+        let gray = Color { r: 128, g: 128, b: 128, a: 255 };
+
+        let blended = |mode| {
+            let ops = vec![rect(Color::YELLOW), RenderOp::PushBlendMode(mode), rect(gray), RenderOp::PopBlendMode];
+            let mut dt = DrawTarget::new(1, 1);
+            render_ops(&ops, &[], &mut [], &mut dt);
+            dt.get_data()[0]
+        };
+
+        assert_eq!(blended(BlendMode::Multiply), 0xFF808000);
+        assert_eq!(blended(BlendMode::Screen), 0xFFFFFF80);
+        assert_eq!(blended(BlendMode::Difference), 0xFF7F7F80);
+        assert_eq!(blended(BlendMode::Darken), 0xFF808000);
+    }
+
+    #[test]
+    fn snapshot() {
+        let bounds = |x0, x1| Bounds {
+            a: Pos { x: x0, y: 0. },
+            b: Pos { x: x1, y: 1. },
+        };
+        let uv = Bounds { a: Pos::ZERO, b: Pos::ONE };
+
+        let ops = vec![
+            RenderOp::FillRect(bounds(0., 2.), FillStyle::SolidColor(Color::RED)),
+            RenderOp::FillRect(bounds(2., 4.), FillStyle::SolidColor(Color::WHITE)),
+            RenderOp::Snapshot(bounds(1., 3.), 0),
+            RenderOp::PushFilters(vec![Filter::Invert(1.)]),
+            RenderOp::FillRect(bounds(1., 3.), FillStyle::Texture(0, uv, ImageRendering::Pixelated)),
+            RenderOp::PopFilters,
+        ];
+
+        let mut textures = [Texture::new(0, 0, Box::new([]))];
+        let mut dt = DrawTarget::new(4, 1);
+        render_ops(&ops[..3], &[], &mut textures, &mut dt);
+
+        // copied & cleared
+        assert_eq!(&*textures[0].pixels, &[0xFFFF0000, 0xFFFFFFFF]);
+        assert_eq!(dt.get_data(), &[0xFFFF0000, 0, 0, 0xFFFFFFFF]);
+
+        // (backdrop-filter)
+        render_ops(&ops, &[], &mut textures, &mut dt);
+        assert_eq!(dt.get_data(), &[0xFFFF0000, 0xFF00FFFF, 0xFF000000, 0xFFFFFFFF]);
+
+        // outside of the target
+        render_ops(&[RenderOp::Snapshot(bounds(-2., 1.), 0)], &[], &mut textures, &mut dt);
+        assert_eq!(&*textures[0].pixels, &[0xFFFF0000; 3]);
+    }
+
+    #[test]
+    fn clip_path_and_mask() {
+        let bounds = |x0, x1| Bounds {
+            a: Pos { x: x0, y: 0. },
+            b: Pos { x: x1, y: 1. },
+        };
+        let fill = || RenderOp::FillRect(bounds(0., 4.), FillStyle::SolidColor(Color::RED));
+
+        let clip = vec![
+            PathCommand::MoveTo(Pos { x: 1., y: 0. }),
+            PathCommand::LineTo(Pos { x: 3., y: 0. }),
+            PathCommand::LineTo(Pos { x: 3., y: 1. }),
+            PathCommand::LineTo(Pos { x: 1., y: 1. }),
+            PathCommand::Close,
+        ];
+        let ops = vec![RenderOp::PushClipPath(clip), fill(), RenderOp::PopClipPath, fill()];
+        let mut dt = DrawTarget::new(4, 1);
+        render_ops(&ops[..3], &[], &mut [], &mut dt);
+        assert_eq!(dt.get_data(), &[0, 0xFFFF0000, 0xFFFF0000, 0]);
+
+        // clip is popped
+        render_ops(&ops, &[], &mut [], &mut dt);
+        assert_eq!(dt.get_data(), &[0xFFFF0000; 4]);
+
+        // left half opaque, right half transparent, nothing outside
+        let texture = Texture::new(2, 1, Box::new([255, 255, 255, 255, 255, 255, 255, 0]));
+        let ops = vec![RenderOp::PushMask(bounds(0., 2.), 0), fill(), RenderOp::PopMask];
+        let mut dt = DrawTarget::new(4, 1);
+        render_ops(&ops, &[], &mut [texture], &mut dt);
+        assert_eq!(dt.get_data()[0], 0xFFFF0000);
+        assert_eq!(dt.get_data()[2..], [0, 0]);
+    }
+
+    #[test]
+    fn partial_repaint() {
+        let mut backend = RaqoteBackend::new(2, 1);
+        let layer = backend.create_layer();
+        let fill = |color| {
+            move |b: &mut Vec<RenderOp>| {
+                b.push_rect(
+                    Bounds {
+                        a: Pos::ZERO,
+                        b: Pos { x: 2., y: 1. },
+                    },
+                    FillStyle::SolidColor(color),
+                );
+            }
+        };
+
+        backend.rebuild_layer_with(layer, fill(Color::RED));
+        backend
+            .render_layer(
+                layer,
+                &[Bounds {
+                    a: Pos::ZERO,
+                    b: Pos { x: 2., y: 1. },
+                }],
+            )
+            .unwrap();
+
+        // only the right pixel
+        backend.rebuild_layer_with(layer, fill(Color::BLUE));
+        backend
+            .render_layer(
+                layer,
+                &[Bounds {
+                    a: Pos { x: 1.5, y: 0. },
+                    b: Pos { x: 2., y: 1. },
+                }],
+            )
+            .unwrap();
+
+        assert_eq!(backend.dt.get_data(), &[0xFFFF0000, 0xFF0000FF]);
+        assert_eq!(backend.pixels(), &[255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn clear_outside_of_root() {
+        let mut backend = RaqoteBackend::new(4, 1);
+        let layer = backend.create_layer();
+        let half = Bounds {
+            a: Pos::ZERO,
+            b: Pos { x: 2., y: 1. },
+        };
+
+        backend.set_clear_color(Color::WHITE);
+        backend.rebuild_layer_with(layer, |b| b.push_rect(half, FillStyle::SolidColor(Color::BLUE)));
+        backend.render_layer(layer, &[half]).unwrap();
+        assert_eq!(backend.pixels(), &[0, 0, 255, 255, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]);
+
+        // everything again, without any damage
+        backend.set_clear_color(Color::BLACK);
+        backend.render_layer(layer, &[]).unwrap();
+        assert_eq!(backend.pixels(), &[0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn translucent_clear_color() {
+        let mut backend = RaqoteBackend::new(1, 1);
+        let layer = backend.create_layer();
+
+        backend.set_clear_color(Color { r: 255, g: 0, b: 0, a: 128 });
+        backend.render_layer(layer, &[]).unwrap();
+
+        // stored premultiplied, read back as it was set
+        assert_eq!(backend.dt.get_data(), &[0x80800000]);
+        assert_eq!(backend.pixels(), &[255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn framebuffer() {
+        let mut backend = RaqoteBackend::new(2, 1);
+        let layer = backend.create_layer();
+
+        backend.set_clear_color(Color::WHITE);
+        backend.rebuild_layer_with(layer, |b| {
+            b.push_rect(Bounds { a: Pos::ZERO, b: Pos::ONE }, FillStyle::SolidColor(Color { r: 0, g: 0, b: 255, a: 128 }));
+        });
+        backend
+            .render_layer(
+                layer,
+                &[Bounds {
+                    a: Pos::ZERO,
+                    b: Pos { x: 2., y: 1. },
+                }],
+            )
+            .unwrap();
+
+        assert_eq!((backend.width(), backend.height()), (2, 1));
+        assert_eq!(backend.pixels(), &[127, 127, 255, 255, 255, 255, 255, 255]);
+
+        let path = std::env::temp_dir().join("raqote_framebuffer.png");
+        backend.save_png(&path).unwrap();
+
+        let decoded = crate::render::decoding::decode_image(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(&decoded.data[..], backend.pixels());
+
+        assert!(backend.save_png("/nonexistent/dir/out.png").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let mut backend = RaqoteBackend::new(1, 1);
+
+        assert!(matches!(
+            backend.create_texture(2, 2, Box::new([0; 4])),
+            Err(RenderError::InvalidImageData { width: 2, height: 2, len: 4 })
+        ));
+        assert!(matches!(backend.create_texture(-1, -4, Box::new([0; 16])), Err(RenderError::InvalidImageData { .. })));
+        assert!(matches!(backend.render_layer(5, &[]), Err(RenderError::Backend(_))));
+    }
 }
@@ -0,0 +1,255 @@
+// cpu filters
+// - pixels are premultiplied argb (same as in the `DrawTarget`)
+// - color functions are 3x4 matrices (working on unpremultiplied rgb)
+
+use super::super::Filter;
+use crate::commons::Pos;
+use crate::render::Color;
+
+type Matrix = [[f32; 4]; 3];
+
+pub fn apply(filter: &Filter, pixels: &mut [u32], width: i32, height: i32) {
+    match *filter {
+        Filter::Blur(sigma) => blur(pixels, width, height, sigma),
+        Filter::DropShadow { offset, blur, color } => drop_shadow(pixels, width, height, offset, blur, color),
+        Filter::Opacity(a) => opacity(pixels, a.clamp(0., 1.)),
+        _ => color_matrix(pixels, &matrix(filter)),
+    }
+}
+
+fn matrix(filter: &Filter) -> Matrix {
+    let amount = |a: f32| a.clamp(0., 1.);
+
+    match *filter {
+        Filter::Brightness(b) => [[b, 0., 0., 0.], [0., b, 0., 0.], [0., 0., b, 0.]],
+        Filter::Contrast(c) => {
+            let o = 0.5 - c * 0.5;
+            [[c, 0., 0., o], [0., c, 0., o], [0., 0., c, o]]
+        }
+        Filter::Grayscale(a) => {
+            let k = 1. - amount(a);
+            [
+                [0.2126 + 0.7874 * k, 0.7152 - 0.7152 * k, 0.0722 - 0.0722 * k, 0.],
+                [0.2126 - 0.2126 * k, 0.7152 + 0.2848 * k, 0.0722 - 0.0722 * k, 0.],
+                [0.2126 - 0.2126 * k, 0.7152 - 0.7152 * k, 0.0722 + 0.9278 * k, 0.],
+            ]
+        }
+        Filter::Sepia(a) => {
+            let k = 1. - amount(a);
+            [
+                [0.393 + 0.607 * k, 0.769 - 0.769 * k, 0.189 - 0.189 * k, 0.],
+                [0.349 - 0.349 * k, 0.686 + 0.314 * k, 0.168 - 0.168 * k, 0.],
+                [0.272 - 0.272 * k, 0.534 - 0.534 * k, 0.131 + 0.869 * k, 0.],
+            ]
+        }
+        Filter::Saturate(s) => [
+            [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s, 0.],
+            [0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s, 0.],
+            [0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s, 0.],
+        ],
+        Filter::HueRotate(deg) => {
+            let (sin, cos) = deg.to_radians().sin_cos();
+            [
+                [
+                    0.213 + cos * 0.787 - sin * 0.213,
+                    0.715 - cos * 0.715 - sin * 0.715,
+                    0.072 - cos * 0.072 + sin * 0.928,
+                    0.,
+                ],
+                [
+                    0.213 - cos * 0.213 + sin * 0.143,
+                    0.715 + cos * 0.285 + sin * 0.140,
+                    0.072 - cos * 0.072 - sin * 0.283,
+                    0.,
+                ],
+                [
+                    0.213 - cos * 0.213 - sin * 0.787,
+                    0.715 - cos * 0.715 + sin * 0.715,
+                    0.072 + cos * 0.928 + sin * 0.072,
+                    0.,
+                ],
+            ]
+        }
+        Filter::Invert(a) => {
+            let a = amount(a);
+            let k = 1. - 2. * a;
+            [[k, 0., 0., a], [0., k, 0., a], [0., 0., k, a]]
+        }
+        Filter::Blur(_) | Filter::DropShadow { .. } | Filter::Opacity(_) => unreachable!(),
+    }
+}
+
+fn color_matrix(pixels: &mut [u32], m: &Matrix) {
+    for px in pixels.iter_mut() {
+        let a = *px >> 24;
+
+        if a == 0 {
+            continue;
+        }
+
+        // unpremultiply
+        let ch = |i: u32| ((*px >> i) & 0xFF) as f32 / a as f32;
+        let (r, g, b) = (ch(16), ch(8), ch(0));
+
+        let res = m.map(|row| (row[0] * r + row[1] * g + row[2] * b + row[3]).clamp(0., 1.));
+        let premul = |c: f32| (c * a as f32 + 0.5) as u32;
+
+        *px = (a << 24) | (premul(res[0]) << 16) | (premul(res[1]) << 8) | premul(res[2]);
+    }
+}
+
+// premultiplied, so all channels are scaled
+fn opacity(pixels: &mut [u32], a: f32) {
+    let k = (a * 256.) as u32;
+
+    for px in pixels.iter_mut() {
+        *px = [24, 16, 8, 0].iter().fold(0, |res, i| res | (((((*px >> i) & 0xFF) * k + 128) >> 8) << i));
+    }
+}
+
+// gaussian approximated with 3 box blurs
+fn blur(pixels: &mut [u32], width: i32, height: i32, sigma: f32) {
+    if sigma <= 0. {
+        return;
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let mut tmp = vec![0; pixels.len()];
+
+    for r in box_radii(sigma) {
+        // rows, then columns
+        box_blur(pixels, &mut tmp, h, w, w, 1, r);
+        box_blur(&tmp, pixels, w, h, 1, w, r);
+    }
+}
+
+// https://www.peterkovesi.com/papers/FastGaussianSmoothing.pdf
+fn box_radii(sigma: f32) -> [usize; 3] {
+    let ideal = (12. * sigma * sigma / 3. + 1.).sqrt();
+    let mut lower = ideal.floor() as i32;
+
+    if lower % 2 == 0 {
+        lower -= 1;
+    }
+
+    let upper = lower + 2;
+    let m = ((12. * sigma * sigma - (3 * lower * lower + 12 * lower + 9) as f32) / (-4 * lower - 4) as f32).round() as i32;
+
+    [0, 1, 2].map(|i| (if i < m { lower } else { upper } as usize) / 2)
+}
+
+// `lines` of `len` pixels, outside is transparent
+fn box_blur(src: &[u32], dest: &mut [u32], lines: usize, len: usize, line_stride: usize, step: usize, r: usize) {
+    let n = (r * 2 + 1) as u32;
+    let channels = |px: u32| [px >> 24, (px >> 16) & 0xFF, (px >> 8) & 0xFF, px & 0xFF];
+
+    for line in 0..lines {
+        let at = |i: usize| line * line_stride + i * step;
+        let mut sum = [0; 4];
+
+        for i in 0..r.min(len) {
+            for (s, c) in sum.iter_mut().zip(channels(src[at(i)])) {
+                *s += c;
+            }
+        }
+
+        for i in 0..len {
+            if i + r < len {
+                for (s, c) in sum.iter_mut().zip(channels(src[at(i + r)])) {
+                    *s += c;
+                }
+            }
+
+            let [a, r_, g, b] = sum.map(|s| (s + n / 2) / n);
+            dest[at(i)] = (a << 24) | (r_ << 16) | (g << 8) | b;
+
+            if i >= r {
+                for (s, c) in sum.iter_mut().zip(channels(src[at(i - r)])) {
+                    *s -= c;
+                }
+            }
+        }
+    }
+}
+
+// shadow from the alpha, blurred & shifted, then the content over it
+fn drop_shadow(pixels: &mut [u32], width: i32, height: i32, offset: Pos, sigma: f32, color: Color) {
+    let (dx, dy) = (offset.x.round() as i32, offset.y.round() as i32);
+    let ca = color.a as u32;
+    let premul = |c: u8, a: u32| c as u32 * ca / 255 * a / 255;
+
+    let mut shadow = vec![0; pixels.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = (x - dx, y - dy);
+
+            if sx >= 0 && sy >= 0 && sx < width && sy < height {
+                let a = pixels[(sy * width + sx) as usize] >> 24;
+                shadow[(y * width + x) as usize] = ((ca * a / 255) << 24) | (premul(color.r, a) << 16) | (premul(color.g, a) << 8) | premul(color.b, a);
+            }
+        }
+    }
+
+    blur(&mut shadow, width, height, sigma);
+
+    for (px, sh) in pixels.iter_mut().zip(shadow) {
+        *px = over(*px, sh);
+    }
+}
+
+// premultiplied `src` over `dest`
+pub fn over(src: u32, dest: u32) -> u32 {
+    let inv = 255 - (src >> 24);
+
+    [24, 16, 8, 0].iter().fold(0, |res, i| {
+        let c = ((src >> i) & 0xFF) + (((dest >> i) & 0xFF) * inv + 127) / 255;
+        res | (c.min(255) << i)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_filters() {
+        let red = 0xFF_FF_00_00;
+
+        let filtered = |f| {
+            let mut px = [red, 0x80_40_00_00, 0];
+            apply(&f, &mut px, 3, 1);
+            px
+        };
+
+        assert_eq!(filtered(Filter::Invert(1.)), [0xFF_00_FF_FF, 0x80_40_80_80, 0]);
+        assert_eq!(filtered(Filter::Brightness(0.5)), [0xFF_80_00_00, 0x80_20_00_00, 0]);
+        assert_eq!(filtered(Filter::Grayscale(1.)), [0xFF_36_36_36, 0x80_0E_0E_0E, 0]);
+        assert_eq!(filtered(Filter::HueRotate(0.)), [red, 0x80_40_00_00, 0]);
+        assert_eq!(filtered(Filter::Opacity(0.5)), [0x80_80_00_00, 0x40_20_00_00, 0]);
+        assert_eq!(filtered(Filter::Opacity(1.)), [red, 0x80_40_00_00, 0]);
+    }
+
+    #[test]
+    fn blur_keeps_sum() {
+        let mut px = vec![0; 21 * 21];
+        px[10 * 21 + 10] = 0xFF_FF_FF_FF;
+
+        blur(&mut px, 21, 21, 1.);
+
+        // spread around, symmetric
+        assert_eq!(px[10 * 21 + 10] >> 24, px[10 * 21 + 10] & 0xFF);
+        assert!(px[10 * 21 + 10] >> 24 < 255);
+        assert_eq!(px[10 * 21 + 9], px[10 * 21 + 11]);
+        assert_eq!(px[9 * 21 + 10], px[10 * 21 + 9]);
+        assert_eq!(px[0], 0);
+    }
+
+    #[test]
+    fn shadow() {
+        let mut px = vec![0xFF_00_00_FF, 0, 0];
+        drop_shadow(&mut px, 3, 1, Pos { x: 1., y: 0. }, 0., Color::BLACK);
+
+        assert_eq!(px, [0xFF_00_00_FF, 0xFF_00_00_00, 0]);
+    }
+}
@@ -0,0 +1,470 @@
+// golden (reference) tests
+// x scenes are rendered into memory & compared with PNGs in `tests/golden`
+// x channels can differ by `TOLERANCE` (AA, rounding, ...)
+// x on failure, `<name>.actual.png` & `<name>.diff.png` are written to `target/golden`
+//   (differing pixels are red, the rest is dimmed)
+// x `BLESS=1 cargo test golden` (re)writes the goldens instead
+
+use super::{write_png, RaqoteBackend};
+use crate::commons::{Bounds, Pos};
+use crate::render::decoding::decode_image;
+use crate::render::*;
+use std::path::{Path, PathBuf};
+
+const SIZE: i32 = 64;
+const TOLERANCE: u8 = 2;
+
+#[test]
+fn backgrounds_and_borders() {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (4., 4., 28., 28.), (36., 4., 60., 28.), (4., 36., 28., 60.), (36., 36., 60., 60.)]);
+    let side = |width, style, color| Some(BorderSide { width, style, color });
+    let uniform = |side| {
+        Some(Border {
+            top: side,
+            right: side,
+            bottom: side,
+            left: side,
+        })
+    };
+
+    r.set_background_color(c[1], Color::RED).unwrap();
+    r.set_border(c[1], uniform(side(2., BorderStyle::Solid, Color::BLUE))).unwrap();
+
+    r.set_background_color(c[2], Color::GREEN).unwrap();
+    r.set_border(c[2], uniform(side(2., BorderStyle::Dashed, Color::BLACK))).unwrap();
+
+    r.set_background_color(c[3], Color::YELLOW).unwrap();
+    r.set_border(c[3], uniform(side(3., BorderStyle::Dotted, Color::BLUE))).unwrap();
+
+    r.set_border(
+        c[4],
+        Some(Border {
+            top: side(6., BorderStyle::Double, Color::BLACK),
+            right: side(6., BorderStyle::Groove, Color::RED),
+            bottom: side(6., BorderStyle::Inset, Color::GREEN),
+            left: side(6., BorderStyle::Outset, Color::BLUE),
+        }),
+    )
+    .unwrap();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("backgrounds_and_borders", r.backend());
+}
+
+#[test]
+fn rounded_borders() {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (4., 4., 28., 28.), (36., 4., 60., 28.), (4., 36., 28., 60.), (36., 36., 60., 60.)]);
+    let side = |width, style, color| Some(BorderSide { width, style, color });
+    let uniform = |side| {
+        Some(Border {
+            top: side,
+            right: side,
+            bottom: side,
+            left: side,
+        })
+    };
+    let radius = |r| {
+        Some(BorderRadius {
+            top_left: r,
+            top_right: r,
+            bottom_right: r,
+            bottom_left: r,
+        })
+    };
+
+    // different widths (elliptic inside), one corner thicker than its radius (square inside)
+    r.set_border(
+        c[1],
+        Some(Border {
+            top: side(2., BorderStyle::Solid, Color::BLUE),
+            right: side(4., BorderStyle::Solid, Color::BLUE),
+            bottom: side(6., BorderStyle::Solid, Color::RED),
+            left: side(2., BorderStyle::Solid, Color::BLUE),
+        }),
+    )
+    .unwrap();
+    r.set_border_radius(c[1], radius(8.)).unwrap();
+
+    r.set_border(c[2], uniform(side(2., BorderStyle::Dashed, Color::BLACK))).unwrap();
+    r.set_border_radius(c[2], radius(8.)).unwrap();
+
+    r.set_border(c[3], uniform(side(3., BorderStyle::Dotted, Color::BLUE))).unwrap();
+    r.set_border_radius(c[3], radius(8.)).unwrap();
+
+    r.set_border(
+        c[4],
+        Some(Border {
+            top: side(6., BorderStyle::Double, Color::BLACK),
+            right: side(6., BorderStyle::Groove, Color::RED),
+            bottom: side(6., BorderStyle::Inset, Color::GREEN),
+            left: side(6., BorderStyle::Outset, Color::BLUE),
+        }),
+    )
+    .unwrap();
+    r.set_border_radius(
+        c[4],
+        Some(BorderRadius {
+            top_left: 10.,
+            top_right: 4.,
+            bottom_right: 10.,
+            bottom_left: 0.,
+        }),
+    )
+    .unwrap();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("rounded_borders", r.backend());
+}
+
+#[test]
+fn outlines_and_shadows() {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (10., 10., 26., 26.), (38., 10., 54., 26.), (10., 38., 54., 54.)]);
+    let gray = Color { r: 0, g: 0, b: 0, a: 128 };
+
+    r.set_background_color(c[1], Color::BLUE).unwrap();
+    r.set_outline(
+        c[1],
+        Some(Outline {
+            width: 2.,
+            offset: 2.,
+            style: OutlineStyle::Solid,
+            color: Color::RED,
+        }),
+    )
+    .unwrap();
+
+    r.set_background_color(c[2], Color::GREEN).unwrap();
+    r.set_outline(
+        c[2],
+        Some(Outline {
+            width: 3.,
+            offset: 1.,
+            style: OutlineStyle::Dotted,
+            color: Color::BLACK,
+        }),
+    )
+    .unwrap();
+    r.set_outline_shadows(
+        c[2],
+        vec![OutlineShadow {
+            offset: Pos { x: 2., y: 2. },
+            // (blur is not implemented yet)
+            blur: 0.,
+            spread: 0.,
+            color: gray,
+        }],
+    )
+    .unwrap();
+
+    r.set_background_color(c[3], Color::YELLOW).unwrap();
+    r.set_outline(
+        c[3],
+        Some(Outline {
+            width: 2.,
+            offset: 1.,
+            style: OutlineStyle::Dashed,
+            color: Color::BLUE,
+        }),
+    )
+    .unwrap();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("outlines_and_shadows", r.backend());
+}
+
+#[test]
+fn images() {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (4., 4., 28., 28.), (36., 4., 60., 28.), (4., 36., 60., 60.)]);
+    let img = r.create_image(8, 8, checkerboard(8, 4)).unwrap();
+    let image = |size, repeat| BackgroundImage::Image {
+        image: img,
+        size,
+        position: BackgroundPosition::CENTER,
+        repeat: (repeat, repeat),
+        origin: BackgroundBox::BorderBox,
+        clip: BackgroundBox::BorderBox,
+    };
+
+    r.set_image_rendering(img, ImageRendering::Pixelated).unwrap();
+    r.set_background_images(c[1], vec![image(BackgroundSize::Fill, BackgroundRepeat::NoRepeat)]).unwrap();
+    r.set_background_images(c[2], vec![image(BackgroundSize::Explicit(Some(6.), None), BackgroundRepeat::Repeat)])
+        .unwrap();
+    r.set_background_images(
+        c[3],
+        vec![BackgroundImage::NineSlice {
+            image: img,
+            slice: SideOffsets::uniform(2.),
+            widths: SideOffsets::uniform(6.),
+            repeat: (NineSliceRepeat::Stretch, NineSliceRepeat::Round),
+            fill: false,
+        }],
+    )
+    .unwrap();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("images", r.backend());
+}
+
+#[test]
+fn effects() {
+    let (mut r, c, bounds) = scene(&[
+        (0., 0., 64., 64.),
+        (0., 24., 64., 40.),
+        (4., 4., 28., 28.),
+        (36., 4., 60., 28.),
+        (4., 36., 28., 60.),
+        (36., 36., 60., 60.),
+        (24., 16., 40., 48.),
+    ]);
+
+    r.set_background_color(c[1], Color::RED).unwrap();
+
+    r.set_background_color(c[2], Color::BLUE).unwrap();
+    r.set_filters(c[2], vec![Filter::Grayscale(1.)]).unwrap();
+
+    r.set_background_color(c[3], Color::YELLOW).unwrap();
+    r.set_blend_mode(c[3], BlendMode::Multiply).unwrap();
+
+    r.set_background_color(c[4], Color::GREEN).unwrap();
+    r.set_clip_path(
+        c[4],
+        Some(ClipPath::Circle {
+            center: Pos { x: 12., y: 12. },
+            radius: 10.,
+        }),
+    )
+    .unwrap();
+
+    r.set_background_color(c[5], Color::BLUE).unwrap();
+    r.set_mask(
+        c[5],
+        Some(Mask::LinearGradient {
+            angle: 90.,
+            stops: vec![(0., Color::TRANSPARENT), (1., Color::BLACK)],
+        }),
+    )
+    .unwrap();
+
+    // frosted glass over all of them, tinted by its own filter
+    r.set_background_color(c[6], Color { r: 255, g: 255, b: 255, a: 96 }).unwrap();
+    r.set_backdrop_filters(c[6], vec![Filter::Blur(2.)]).unwrap();
+    r.set_filters(c[6], vec![Filter::Sepia(1.)]).unwrap();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("effects", r.backend());
+}
+
+#[test]
+fn stacking() {
+    let (mut r, c, bounds) = stacking_scene();
+
+    r.render_container(c[0], &bounds).unwrap();
+    check("stacking", r.backend());
+}
+
+#[test]
+fn partial_repaint() {
+    let (mut r, c, bounds) = stacking_scene();
+    r.render_container(c[0], &bounds).unwrap();
+
+    r.set_background_color(c[2], Color::BLACK).unwrap();
+    r.set_z_index(c[3], Some(2)).unwrap();
+    r.render_container(c[0], &bounds).unwrap();
+    check("partial_repaint", r.backend());
+
+    // same as if it was rendered from scratch
+    let (mut fresh, c, bounds) = stacking_scene();
+    fresh.set_background_color(c[2], Color::BLACK).unwrap();
+    fresh.set_z_index(c[3], Some(2)).unwrap();
+    fresh.render_container(c[0], &bounds).unwrap();
+    check("partial_repaint", fresh.backend());
+}
+
+#[test]
+fn filtered_repaint() {
+    let (mut r, c, bounds) = filtered_scene(Color::BLUE);
+    r.render_container(c[0], &bounds).unwrap();
+
+    // blur spreads the change outside of the child
+    r.set_background_color(c[2], Color::RED).unwrap();
+    r.render_container(c[0], &bounds).unwrap();
+    check("filtered_repaint", r.backend());
+
+    let (mut fresh, c, bounds) = filtered_scene(Color::RED);
+    fresh.render_container(c[0], &bounds).unwrap();
+    check("filtered_repaint", fresh.backend());
+}
+
+#[test]
+fn backdrop_repaint() {
+    let (mut r, c, bounds) = backdrop_scene(Color::RED);
+    r.render_container(c[0], &bounds).unwrap();
+
+    // the whole blurred area has to be redrawn (not the outline from the last frame)
+    r.set_background_color(c[1], Color::BLUE).unwrap();
+    r.render_container(c[0], &bounds).unwrap();
+    check("backdrop_repaint", r.backend());
+
+    let (mut fresh, c, bounds) = backdrop_scene(Color::BLUE);
+    fresh.render_container(c[0], &bounds).unwrap();
+    check("backdrop_repaint", fresh.backend());
+}
+
+#[test]
+fn tolerance() {
+    let (_, count) = diff(&[10, 10, 10, 255], &[12, 8, 10, 255]);
+    assert_eq!(count, 0);
+
+    let (pixels, count) = diff(&[10, 10, 10, 255, 0, 0, 0, 0], &[13, 10, 10, 255, 0, 0, 0, 0]);
+    assert_eq!(count, 1);
+    assert_eq!(pixels[..4], [255, 0, 0, 255]);
+}
+
+fn stacking_scene() -> (Renderer<RaqoteBackend, usize>, Vec<ContainerId>, Vec<Bounds>) {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (8., 8., 40., 40.), (16., 16., 48., 48.), (24., 24., 56., 56.)]);
+
+    r.set_background_color(c[1], Color::RED).unwrap();
+    r.set_z_index(c[1], Some(1)).unwrap();
+    r.set_background_color(c[2], Color::GREEN).unwrap();
+    r.set_background_color(c[3], Color::BLUE).unwrap();
+    r.set_z_index(c[3], Some(-1)).unwrap();
+
+    (r, c, bounds)
+}
+
+// blurred parent with a child
+fn filtered_scene(color: Color) -> (Renderer<RaqoteBackend, usize>, Vec<ContainerId>, Vec<Bounds>) {
+    let (mut r, mut c, mut bounds) = scene(&[(0., 0., 64., 64.), (8., 8., 56., 56.)]);
+
+    c.push(r.create_container(2));
+    bounds.push(Bounds {
+        a: Pos { x: 16., y: 16. },
+        b: Pos { x: 32., y: 32. },
+    });
+
+    r.insert_child(c[1], 0, Child::Container(c[2])).unwrap();
+    r.set_background_color(c[1], Color::YELLOW).unwrap();
+    r.set_filters(c[1], vec![Filter::Blur(2.)]).unwrap();
+    r.set_background_color(c[2], color).unwrap();
+
+    (r, c, bounds)
+}
+
+// changing node partly under an outlined backdrop
+fn backdrop_scene(color: Color) -> (Renderer<RaqoteBackend, usize>, Vec<ContainerId>, Vec<Bounds>) {
+    let (mut r, c, bounds) = scene(&[(0., 0., 64., 64.), (4., 20., 28., 44.), (24., 16., 48., 48.)]);
+
+    r.set_background_color(c[1], color).unwrap();
+    r.set_backdrop_filters(c[2], vec![Filter::Blur(2.)]).unwrap();
+    r.set_outline(
+        c[2],
+        Some(Outline {
+            width: 3.,
+            offset: 0.,
+            style: OutlineStyle::Solid,
+            color: Color::BLACK,
+        }),
+    )
+    .unwrap();
+
+    (r, c, bounds)
+}
+
+// first one is the root, the rest are its children
+fn scene(rects: &[(f32, f32, f32, f32)]) -> (Renderer<RaqoteBackend, usize>, Vec<ContainerId>, Vec<Bounds>) {
+    let mut backend = RaqoteBackend::new(SIZE, SIZE);
+    backend.set_clear_color(Color::WHITE);
+
+    let mut r = Renderer::new(backend);
+    let bounds: Vec<_> = rects
+        .iter()
+        .map(|&(x0, y0, x1, y1)| Bounds {
+            a: Pos { x: x0, y: y0 },
+            b: Pos { x: x1, y: y1 },
+        })
+        .collect();
+    let containers: Vec<_> = (0..bounds.len()).map(|i| r.create_container(i)).collect();
+
+    for (i, ch) in containers[1..].iter().enumerate() {
+        r.insert_child(containers[0], i, Child::Container(*ch)).unwrap();
+    }
+
+    (r, containers, bounds)
+}
+
+fn checkerboard(size: usize, square_size: usize) -> Box<[u8]> {
+    let mut data = Vec::new();
+
+    for y in 0..size {
+        for x in 0..size {
+            let v = if (x / square_size) % 2 == (y / square_size) % 2 { 0xFF } else { 0x00 };
+            data.extend([v, 0x80, 0xFF - v, 0xFF]);
+        }
+    }
+
+    data.into_boxed_slice()
+}
+
+fn check(name: &str, backend: &RaqoteBackend) {
+    let golden = path("tests/golden").join(format!("{}.png", name));
+
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        backend.save_png(&golden).unwrap();
+        return;
+    }
+
+    let expected = match std::fs::read(&golden) {
+        Ok(bytes) => decode_image(&bytes).unwrap(),
+        Err(_) => panic!("missing {}, run with BLESS=1 to create it", golden.display()),
+    };
+
+    let out = path("target/golden");
+    std::fs::create_dir_all(&out).unwrap();
+
+    let actual = out.join(format!("{}.actual.png", name));
+
+    if (expected.width, expected.height) != (backend.width(), backend.height()) {
+        backend.save_png(&actual).unwrap();
+        panic!(
+            "{}: size {}x{} != {}x{} (see {})",
+            name,
+            backend.width(),
+            backend.height(),
+            expected.width,
+            expected.height,
+            actual.display()
+        );
+    }
+
+    let (pixels, count) = diff(&expected.data, backend.pixels());
+
+    if count > 0 {
+        let diff = out.join(format!("{}.diff.png", name));
+        backend.save_png(&actual).unwrap();
+        write_png(&diff, backend.width(), backend.height(), &pixels).unwrap();
+        panic!("{}: {} pixels differ (see {})", name, count, diff.display());
+    }
+}
+
+// returns (diff image, number of differing pixels)
+fn diff(expected: &[u8], actual: &[u8]) -> (Vec<u8>, usize) {
+    let mut res = Vec::with_capacity(actual.len());
+    let mut count = 0;
+
+    for (e, a) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > TOLERANCE) {
+            res.extend([255, 0, 0, 255]);
+            count += 1;
+        } else {
+            let gray = (a[..3].iter().map(|&c| c as u32).sum::<u32>() / 3 * a[3] as u32 / 255) as u8;
+            res.extend([gray / 4 + 191, gray / 4 + 191, gray / 4 + 191, 255]);
+        }
+    }
+
+    (res, count)
+}
+
+fn path(rel: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(rel)
+}
@@ -0,0 +1,270 @@
+// background image geometry
+// - both axes are resolved separately and then combined
+// - uv outside of 0..1 means the texture should be repeated
+
+use super::{BackgroundPosition, BackgroundRepeat, BackgroundSize, NineSliceRepeat, SideOffsets};
+use crate::commons::{Au, Bounds, Pos};
+
+// (dest start, dest end, uv start, uv end)
+type Span = (Au, Au, f32, f32);
+
+// returns (bounds, uv) pairs
+pub fn image_rects(
+    image_size: (f32, f32),
+    size: BackgroundSize,
+    position: BackgroundPosition,
+    repeat: (BackgroundRepeat, BackgroundRepeat),
+    origin: Bounds,
+    clip: Bounds,
+) -> Vec<(Bounds, Bounds)> {
+    let (mut w, mut h) = tile_size(image_size, size, origin);
+
+    if w <= 0. || h <= 0. {
+        return Vec::new();
+    }
+
+    // if only one axis is round and the other one is auto, aspect ratio is kept
+    let auto_w = matches!(size, BackgroundSize::Auto | BackgroundSize::Explicit(None, _));
+    let auto_h = matches!(size, BackgroundSize::Auto | BackgroundSize::Explicit(_, None));
+    let round_w = rounded(w, origin.width());
+    let round_h = rounded(h, origin.height());
+
+    match repeat {
+        (BackgroundRepeat::Round, BackgroundRepeat::Round) => {
+            w = round_w;
+            h = round_h;
+        }
+        (BackgroundRepeat::Round, _) => {
+            if auto_h {
+                h *= round_w / w;
+            }
+            w = round_w;
+        }
+        (_, BackgroundRepeat::Round) => {
+            if auto_w {
+                w *= round_h / h;
+            }
+            h = round_h;
+        }
+        _ => {}
+    }
+
+    let xs = spans(origin.a.x, origin.width(), clip.a.x, clip.b.x, w, position.align_x, position.offset.x, repeat.0);
+    let ys = spans(origin.a.y, origin.height(), clip.a.y, clip.b.y, h, position.align_y, position.offset.y, repeat.1);
+
+    let mut res = Vec::new();
+
+    for &(y0, y1, v0, v1) in &ys {
+        for &(x0, x1, u0, u1) in &xs {
+            res.push((
+                Bounds {
+                    a: Pos { x: x0, y: y0 },
+                    b: Pos { x: x1, y: y1 },
+                },
+                Bounds {
+                    a: Pos { x: u0, y: v0 },
+                    b: Pos { x: u1, y: v1 },
+                },
+            ));
+        }
+    }
+
+    res
+}
+
+// border-image, corners are always stretched
+// returns (bounds, uv) pairs
+pub fn nine_slice_rects(
+    (iw, ih): (f32, f32),
+    slice: SideOffsets,
+    widths: SideOffsets,
+    repeat: (NineSliceRepeat, NineSliceRepeat),
+    fill: bool,
+    bounds: Bounds,
+) -> Vec<(Bounds, Bounds)> {
+    if iw <= 0. || ih <= 0. || slice.left + slice.right > iw || slice.top + slice.bottom > ih {
+        return Vec::new();
+    }
+
+    let xs = [bounds.a.x, bounds.a.x + widths.left, bounds.b.x - widths.right, bounds.b.x];
+    let ys = [bounds.a.y, bounds.a.y + widths.top, bounds.b.y - widths.bottom, bounds.b.y];
+    let us = [0., slice.left / iw, (iw - slice.right) / iw, 1.];
+    let vs = [0., slice.top / ih, (ih - slice.bottom) / ih, 1.];
+
+    // edges are scaled to the border width first
+    let scale = |w: f32, s: f32| if s > 0. { w / s } else { 0. };
+    let (top, bottom) = (scale(widths.top, slice.top), scale(widths.bottom, slice.bottom));
+    let (left, right) = (scale(widths.left, slice.left), scale(widths.right, slice.right));
+    let (center_w, center_h) = (iw - slice.left - slice.right, ih - slice.top - slice.bottom);
+
+    // center uses top/left scale (unless it's zero)
+    let h_tiles = |scale: f32| tiles(xs[1], xs[2], center_w * scale, repeat.0, us[1], us[2]);
+    let v_tiles = |scale: f32| tiles(ys[1], ys[2], center_h * scale, repeat.1, vs[1], vs[2]);
+    let stretched = |i: usize, ps: &[Au; 4], uvs: &[f32; 4]| vec![(ps[i], ps[i + 1], uvs[i], uvs[i + 1])];
+
+    let columns = [stretched(0, &xs, &us), h_tiles(top), stretched(2, &xs, &us)];
+    let rows = [stretched(0, &ys, &vs), v_tiles(left), stretched(2, &ys, &vs)];
+    let bottom_columns = h_tiles(bottom);
+    let right_rows = v_tiles(right);
+
+    let mut res = Vec::new();
+
+    for (row, y_spans) in rows.iter().enumerate() {
+        for (col, x_spans) in columns.iter().enumerate() {
+            let (x_spans, y_spans) = match (row, col) {
+                (1, 1) if !fill => continue,
+                (1, 1) => (if top > 0. { x_spans } else { &bottom_columns }, if left > 0. { y_spans } else { &right_rows }),
+                (2, 1) => (&bottom_columns, y_spans),
+                (1, 2) => (x_spans, &right_rows),
+                _ => (x_spans, y_spans),
+            };
+
+            for &(y0, y1, v0, v1) in y_spans {
+                for &(x0, x1, u0, u1) in x_spans {
+                    res.push((
+                        Bounds {
+                            a: Pos { x: x0, y: y0 },
+                            b: Pos { x: x1, y: y1 },
+                        },
+                        Bounds {
+                            a: Pos { x: u0, y: v0 },
+                            b: Pos { x: u1, y: v1 },
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    res
+}
+
+// `start..end` filled with `uv0..uv1` part of the texture
+fn tiles(start: Au, end: Au, tile: f32, repeat: NineSliceRepeat, uv0: f32, uv1: f32) -> Vec<Span> {
+    let len = end - start;
+
+    if len <= 0. {
+        return Vec::new();
+    }
+
+    if tile <= 0. || repeat == NineSliceRepeat::Stretch {
+        return vec![(start, end, uv0, uv1)];
+    }
+
+    let (first, tile, gap) = match repeat {
+        NineSliceRepeat::Repeat => {
+            let first = (start + end - tile) / 2.;
+            (first - ((first - start) / tile).ceil() * tile, tile, 0.)
+        }
+        NineSliceRepeat::Round => (start, len / (len / tile).round().max(1.), 0.),
+        _ => {
+            let count = (len / tile).floor();
+            let gap = (len - count * tile) / (count + 1.);
+            (start + gap, tile, gap)
+        }
+    };
+
+    let mut res = Vec::new();
+    let mut s = first;
+
+    // the epsilon is for rounding errors
+    while s + 0.001 < end {
+        let (a, b) = (s.max(start), (s + tile).min(end));
+
+        if b > a {
+            let uv = |p: Au| uv0 + (p - s) / tile * (uv1 - uv0);
+            res.push((a, b, uv(a), uv(b)));
+        }
+
+        s += tile + gap;
+    }
+
+    res
+}
+
+fn tile_size((iw, ih): (f32, f32), size: BackgroundSize, origin: Bounds) -> (f32, f32) {
+    let (ow, oh) = (origin.width(), origin.height());
+
+    if iw <= 0. || ih <= 0. {
+        return (0., 0.);
+    }
+
+    match size {
+        BackgroundSize::Auto | BackgroundSize::Explicit(None, None) => (iw, ih),
+        BackgroundSize::Contain => {
+            let scale = (ow / iw).min(oh / ih);
+            (iw * scale, ih * scale)
+        }
+        BackgroundSize::Cover => {
+            let scale = (ow / iw).max(oh / ih);
+            (iw * scale, ih * scale)
+        }
+        BackgroundSize::Explicit(Some(w), Some(h)) => (w, h),
+        BackgroundSize::Explicit(Some(w), None) => (w, w * ih / iw),
+        BackgroundSize::Explicit(None, Some(h)) => (h * iw / ih, h),
+        BackgroundSize::Fill => (ow, oh),
+    }
+}
+
+// tile size so that it fits whole number of times
+fn rounded(tile: f32, len: f32) -> f32 {
+    len / (len / tile).round().max(1.)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spans(start: Au, len: Au, clip_a: Au, clip_b: Au, tile: f32, align: f32, offset: Au, repeat: BackgroundRepeat) -> Vec<Span> {
+    let pos = start + align * (len - tile) + offset;
+    let count = (len / tile).floor();
+
+    match repeat {
+        // one span is enough, the texture will repeat itself
+        BackgroundRepeat::Repeat | BackgroundRepeat::Round => clipped(clip_a, clip_b, clip_a, clip_b, pos, tile).into_iter().collect(),
+
+        // first & last tile touch the edges, position is ignored
+        BackgroundRepeat::Space if count >= 2. => {
+            let step = tile + (len - count * tile) / (count - 1.);
+            let first = ((clip_a - start) / step).floor() as i32;
+            let last = ((clip_b - start) / step).ceil() as i32;
+
+            (first..last)
+                .filter_map(|i| {
+                    let s = start + i as f32 * step;
+                    clipped(s, s + tile, clip_a, clip_b, s, tile)
+                })
+                .collect()
+        }
+
+        BackgroundRepeat::NoRepeat | BackgroundRepeat::Space => clipped(pos, pos + tile, clip_a, clip_b, pos, tile).into_iter().collect(),
+    }
+}
+
+// `s..e` clipped to `clip_a..clip_b` with uv relative to the tile at `pos`
+fn clipped(s: Au, e: Au, clip_a: Au, clip_b: Au, pos: Au, tile: f32) -> Option<Span> {
+    let (a, b) = (s.max(clip_a), e.min(clip_b));
+
+    if a < b {
+        Some((a, b, (a - pos) / tile, (b - pos) / tile))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nine_slice_tiles() {
+        // centered, partial at both ends
+        assert_eq!(
+            tiles(0., 50., 20., NineSliceRepeat::Repeat, 0., 1.),
+            vec![(0., 15., 0.25, 1.), (15., 35., 0., 1.), (35., 50., 0., 0.75)]
+        );
+
+        // 3 tiles, 10 left for 4 gaps
+        assert_eq!(
+            tiles(0., 70., 20., NineSliceRepeat::Space, 0.5, 1.),
+            vec![(2.5, 22.5, 0.5, 1.), (25., 45., 0.5, 1.), (47.5, 67.5, 0.5, 1.)]
+        );
+    }
+}
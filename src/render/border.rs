@@ -0,0 +1,778 @@
+// border & outline rendering
+// - every edge is a trapezoid along one side of the box
+// - rounded corners are approximated with triangles (split in the middle, between the two edges)
+// - styles are made of rects & triangles (and round dots, using msdf circle)
+
+use super::backend::{FillStyle, LayerBuilder, RenderBackend};
+use super::{Border, BorderRadius, BorderSide, BorderStyle, Color, Outline, OutlineStyle, RenderContext, RenderError};
+use crate::commons::{Bounds, Pos};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::ops::Index;
+
+// msdf circle used for dots
+pub const CIRCLE_SIZE: i32 = 32;
+// how many texels is the whole 0..1 distance range
+const CIRCLE_RANGE: f32 = 8.;
+
+// max length of one arc segment
+const ARC_STEP: f32 = 4.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+const SIDES: [Side; 4] = [Side::Top, Side::Right, Side::Bottom, Side::Left];
+
+// outward direction of top-left, top-right, bottom-right & bottom-left corner
+const CORNER_DIRS: [(f32, f32); 4] = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)];
+
+// vertical & horizontal side meeting in each corner
+const CORNER_SIDES: [(Side, Side); 4] = [
+    (Side::Left, Side::Top),
+    (Side::Right, Side::Top),
+    (Side::Right, Side::Bottom),
+    (Side::Left, Side::Bottom),
+];
+
+// part of the edge (between two fractions of its thickness), split into
+// the rect in the middle & two triangles in the (mitered) corners
+struct Trapezoid {
+    rect: Bounds,
+    start: [Pos; 3],
+    end: [Pos; 3],
+}
+
+// border box, with its (fitted) radii & edge widths
+#[derive(Clone, Copy)]
+struct BorderBox {
+    bounds: Bounds,
+    radii: [f32; 4],
+    widths: [f32; 4],
+}
+
+impl<RB: RenderBackend, BK: Copy, BS: Index<BK, Output = Bounds>> RenderContext<'_, RB, BK, BS> {
+    pub(super) fn render_border(&mut self, border: &Border, radius: Option<&BorderRadius>) -> Result<(), RenderError> {
+        // note the border is always inside (it acts like padding in layout)
+        let sides = [border.top, border.right, border.bottom, border.left];
+        let radii = radius.map(|r| fit_radii(self.current_bounds, r)).unwrap_or([0.; 4]);
+
+        self.render_edges(self.current_bounds, radii, sides)
+    }
+
+    pub(super) fn render_outline(&mut self, outline: &Outline, radius: Option<&BorderRadius>) -> Result<(), RenderError> {
+        let Outline { width, offset, style, color } = *outline;
+        let inner = self.current_bounds.inflate_uniform(offset);
+
+        let style = match style {
+            OutlineStyle::Solid => BorderStyle::Solid,
+            OutlineStyle::Dashed => BorderStyle::Dashed,
+            OutlineStyle::Dotted => BorderStyle::Dotted,
+            OutlineStyle::Double => BorderStyle::Double,
+        };
+
+        // outline follows the border box corners, shifted by the offset
+        // (square ones stay square, even if the others are rounded)
+        let radii = radius.map(|r| fit_radii(self.current_bounds, r)).unwrap_or([0.; 4]);
+
+        if radii.iter().all(|r| *r <= 0.) {
+            // single color, so there's no need for the diagonal joins
+            match style {
+                BorderStyle::Solid => self.push_frame(inner, 0., width, color),
+                BorderStyle::Double => {
+                    self.push_frame(inner, width / 3. * 2., width, color);
+                    self.push_frame(inner, 0., width / 3., color);
+                }
+                _ => {
+                    let side = Some(BorderSide { width, style, color });
+                    self.render_edges(inner.inflate_uniform(width), [0.; 4], [side; 4])?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        let radii = radii.map(|r| if r > 0. { (r + offset).max(0.) } else { 0. });
+        self.render_rounded_outline(inner, radii, width, style, color)
+    }
+
+    // `inner` is the inner edge of the outline
+    fn render_rounded_outline(&mut self, inner: Bounds, radii: [f32; 4], width: f32, style: BorderStyle, color: Color) -> Result<(), RenderError> {
+        let outer = inner.inflate_uniform(width);
+
+        match style {
+            BorderStyle::Double => {
+                self.push_rounded_band(inner, radii, width / 3. * 2., width, color);
+                self.push_rounded_band(inner, radii, 0., width / 3., color);
+            }
+
+            BorderStyle::Dashed => {
+                // corners are always part of the dash (same as for square corners)
+                for (i, &(center, start)) in corners(inner, radii).iter().enumerate() {
+                    self.push_corner(i, center, start, radii[i], 0., width, color);
+                }
+
+                for side in SIDES {
+                    let rect = straight(inner, radii, side, 0., width);
+                    let (min, max) = along(rect, side);
+                    let offset = along(outer, side).0;
+
+                    for (a, b) in dashes(length(outer, side), width * 3.) {
+                        let (a, b) = ((offset + a).max(min), (offset + b).min(max));
+
+                        if a < b {
+                            self.push_rect(segment(rect, side, a, b), color);
+                        }
+                    }
+                }
+            }
+
+            BorderStyle::Dotted => {
+                let texture = self.circle_texture()?;
+                let mut dots = Vec::new();
+
+                // corners own the dots at their ends
+                for (i, &(center, start)) in corners(inner, radii).iter().enumerate() {
+                    if radii[i] <= 0. {
+                        let (sx, sy) = CORNER_DIRS[i];
+                        let (x, y) = (center.x + sx * width / 2., center.y + sy * width / 2.);
+
+                        dots.push(dot(Pos { x, y }, width));
+                        continue;
+                    }
+
+                    let r = radii[i] + width / 2.;
+
+                    for (a, b) in dashes(r * FRAC_PI_2, width) {
+                        dots.push(dot(arc_point(center, start + (a + b) / 2. / r, r), width));
+                    }
+                }
+
+                for side in SIDES {
+                    let rect = straight(inner, radii, side, 0., width);
+                    let (min, max) = along(rect, side);
+                    let span = dashes(max - min + width, width);
+
+                    for (a, b) in span.iter().skip(1).take(span.len().saturating_sub(2)) {
+                        let center = min - width / 2. + (a + b) / 2.;
+                        dots.push(segment(rect, side, center - width / 2., center + width / 2.));
+                    }
+                }
+
+                for dot in dots {
+                    self.push_dot(texture, dot, color);
+                }
+            }
+
+            // outlines can't be anything else
+            _ => self.push_rounded_band(inner, radii, 0., width, color),
+        }
+
+        Ok(())
+    }
+
+    // four rects around the `inner` bounds, between `from` & `to` (distances from the inner edge)
+    fn push_frame(&mut self, inner: Bounds, from: f32, to: f32, color: Color) {
+        let (o, i) = (inner.inflate_uniform(to), inner.inflate_uniform(from));
+        let p = |x, y| Pos { x, y };
+
+        self.push_rect(Bounds { a: o.a, b: p(o.b.x, i.a.y) }, color);
+        self.push_rect(
+            Bounds {
+                a: p(i.b.x, i.a.y),
+                b: p(o.b.x, i.b.y),
+            },
+            color,
+        );
+        self.push_rect(Bounds { a: p(o.a.x, i.b.y), b: o.b }, color);
+        self.push_rect(
+            Bounds {
+                a: p(o.a.x, i.a.y),
+                b: p(i.a.x, i.b.y),
+            },
+            color,
+        );
+    }
+
+    // ring between `from` & `to` (distances from the inner edge)
+    fn push_rounded_band(&mut self, inner: Bounds, radii: [f32; 4], from: f32, to: f32, color: Color) {
+        for side in SIDES {
+            self.push_rect(straight(inner, radii, side, from, to), color);
+        }
+
+        for (i, &(center, start)) in corners(inner, radii).iter().enumerate() {
+            self.push_corner(i, center, start, radii[i], from, to, color);
+        }
+    }
+
+    // part of the i-th corner, between `from` & `to` (distances from the inner edge)
+    // square corners (zero radius) are mitered, like they'd be without any radius
+    #[allow(clippy::too_many_arguments)]
+    fn push_corner(&mut self, i: usize, center: Pos, start: f32, radius: f32, from: f32, to: f32, color: Color) {
+        if radius > 0. {
+            return self.push_arc(center, start, radius + from, radius + to, color);
+        }
+
+        // L-shaped (or the whole square if `from` is zero)
+        let (sx, sy) = CORNER_DIRS[i];
+        let rect = |x0: f32, y0: f32, x1: f32, y1: f32| Bounds {
+            a: Pos {
+                x: center.x + (sx * x0).min(sx * x1),
+                y: center.y + (sy * y0).min(sy * y1),
+            },
+            b: Pos {
+                x: center.x + (sx * x0).max(sx * x1),
+                y: center.y + (sy * y0).max(sy * y1),
+            },
+        };
+
+        self.push_rect(rect(from, 0., to, to), color);
+        self.push_rect(rect(0., from, from, to), color);
+    }
+
+    // quarter of the ring, starting at `start` angle (clockwise)
+    fn push_arc(&mut self, center: Pos, start: f32, r0: f32, r1: f32, color: Color) {
+        let n = (r1 * FRAC_PI_2 / ARC_STEP).ceil().max(1.) as usize;
+        let angles = (0..=n).map(|i| start + FRAC_PI_2 * i as f32 / n as f32);
+
+        let inner: Vec<_> = angles.clone().map(|a| arc_point(center, a, r0)).collect();
+        let outer: Vec<_> = angles.map(|a| arc_point(center, a, r1)).collect();
+
+        self.push_strip(&inner, &outer, color);
+    }
+
+    // triangles between two polylines (with the same number of points)
+    fn push_strip(&mut self, inner: &[Pos], outer: &[Pos], color: Color) {
+        for i in 1..inner.len().min(outer.len()) {
+            let (i0, o0, i1, o1) = (inner[i - 1], outer[i - 1], inner[i], outer[i]);
+
+            self.push_triangle([i0, o0, o1], color);
+            self.push_triangle([i0, o1, i1], color);
+        }
+    }
+
+    // edges are drawn inside of the `bounds`, meeting diagonally in the corners
+    // so that nothing is drawn twice
+    fn render_edges(&mut self, bounds: Bounds, radii: [f32; 4], sides: [Option<BorderSide>; 4]) -> Result<(), RenderError> {
+        let widths = sides.map(|s| match s {
+            Some(BorderSide { width, style, .. }) if style != BorderStyle::None => width,
+            _ => 0.,
+        });
+        let border_box = BorderBox { bounds, radii, widths };
+
+        for (i, side) in sides.iter().enumerate() {
+            if let Some(s) = side {
+                if widths[i] > 0. {
+                    self.render_edge(&border_box, SIDES[i], *s)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_edge(&mut self, border_box: &BorderBox, side: Side, BorderSide { width, style, color }: BorderSide) -> Result<(), RenderError> {
+        // top & left are in the "shadow"
+        let lit = side == Side::Bottom || side == Side::Right;
+        let bounds = border_box.bounds;
+
+        match style {
+            BorderStyle::None => {}
+            BorderStyle::Solid => self.push_band(border_box, side, 0., 1., color),
+
+            BorderStyle::Inset | BorderStyle::Outset => {
+                let color = if lit == (style == BorderStyle::Inset) { light(color) } else { dark(color) };
+                self.push_band(border_box, side, 0., 1., color);
+            }
+
+            BorderStyle::Groove | BorderStyle::Ridge => {
+                let (outer, inner) = if lit == (style == BorderStyle::Groove) {
+                    (light(color), dark(color))
+                } else {
+                    (dark(color), light(color))
+                };
+
+                self.push_band(border_box, side, 0., 0.5, outer);
+                self.push_band(border_box, side, 0.5, 1., inner);
+            }
+
+            BorderStyle::Double => {
+                self.push_band(border_box, side, 0., 1. / 3., color);
+                self.push_band(border_box, side, 2. / 3., 1., color);
+            }
+
+            BorderStyle::Dashed => {
+                // corners are always part of the first/last dash
+                let rect = if border_box.is_rounded() {
+                    for i in side_corners(side) {
+                        self.push_corner_band(border_box, i, side, 0., 1., color);
+                    }
+
+                    border_box.straight(side, 0., 1.)
+                } else {
+                    let Trapezoid { rect, start, end } = trapezoid(bounds, border_box.widths, side, 0., 1.);
+
+                    self.push_triangle(start, color);
+                    self.push_triangle(end, color);
+                    rect
+                };
+                let (min, max) = along(rect, side);
+                let offset = along(bounds, side).0;
+
+                for (a, b) in dashes(length(bounds, side), width * 3.) {
+                    let (a, b) = ((offset + a).max(min), (offset + b).min(max));
+
+                    if a < b {
+                        self.push_rect(segment(rect, side, a, b), color);
+                    }
+                }
+            }
+
+            BorderStyle::Dotted if border_box.is_rounded() => {
+                let texture = self.circle_texture()?;
+                let rect = border_box.straight(side, 0., 1.);
+
+                // corners own the dots at their ends
+                for i in side_corners(side) {
+                    for center in border_box.corner_dots(i, side, width) {
+                        self.push_dot(texture, dot(center, width), color);
+                    }
+                }
+
+                // dots are in the middle of the thickness, so they end where the middle of the corner does
+                let middle = BorderBox {
+                    widths: border_box.widths.map(|w| w / 2.),
+                    ..*border_box
+                };
+                let (min, max) = along(middle.straight(side, 0., 1.), side);
+                let span = dashes(max - min + width, width);
+
+                for (a, b) in span.iter().skip(1).take(span.len().saturating_sub(2)) {
+                    let center = min - width / 2. + (a + b) / 2.;
+                    self.push_dot(texture, segment(rect, side, center - width / 2., center + width / 2.), color);
+                }
+            }
+
+            BorderStyle::Dotted => {
+                let texture = self.circle_texture()?;
+                let rect = trapezoid(bounds, border_box.widths, side, 0., 1.).rect;
+                let (min, max) = along(rect, side);
+                let offset = along(bounds, side).0;
+
+                for (a, b) in dashes(length(bounds, side), width) {
+                    let center = offset + (a + b) / 2.;
+
+                    // corner dots belong to the top/bottom edges
+                    if matches!(side, Side::Left | Side::Right) && (center < min || center > max) {
+                        continue;
+                    }
+
+                    // dots are always round, even if the edge is shorter
+                    self.push_dot(texture, segment(rect, side, center - width / 2., center + width / 2.), color);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // part of the edge between `from` & `to` (fractions of the thickness, from the outer side)
+    fn push_band(&mut self, border_box: &BorderBox, side: Side, from: f32, to: f32, color: Color) {
+        if !border_box.is_rounded() {
+            return self.push_trapezoid(trapezoid(border_box.bounds, border_box.widths, side, from, to), color);
+        }
+
+        let [first, last] = side_corners(side);
+
+        self.push_corner_band(border_box, first, side, from, to, color);
+        self.push_rect(border_box.straight(side, from, to), color);
+        self.push_corner_band(border_box, last, side, from, to, color);
+    }
+
+    // half of the i-th corner (the one next to the `side`), between `from` & `to`
+    fn push_corner_band(&mut self, border_box: &BorderBox, i: usize, side: Side, from: f32, to: f32, color: Color) {
+        let inner = border_box.corner_half(i, side, to);
+        let outer = border_box.corner_half(i, side, from);
+
+        self.push_strip(&inner, &outer, color);
+    }
+
+    // created (before anything dotted is set) by the renderer
+    fn circle_texture(&self) -> Result<RB::TextureId, RenderError> {
+        self.ui_state.circle_texture.ok_or_else(|| RenderError::Backend("missing circle texture".to_string()))
+    }
+
+    fn push_dot(&mut self, texture: RB::TextureId, dot: Bounds, color: Color) {
+        self.builder.push_rect(
+            dot,
+            FillStyle::Msdf {
+                texture,
+                uv: Bounds { a: Pos::ZERO, b: Pos::ONE },
+                factor: CIRCLE_RANGE * dot.width() / CIRCLE_SIZE as f32,
+                color,
+            },
+        );
+    }
+
+    fn push_trapezoid(&mut self, Trapezoid { rect, start, end }: Trapezoid, color: Color) {
+        self.push_triangle(start, color);
+        self.push_rect(rect, color);
+        self.push_triangle(end, color);
+    }
+
+    fn push_rect(&mut self, bounds: Bounds, color: Color) {
+        if bounds.width() > 0. && bounds.height() > 0. {
+            self.builder.push_rect(bounds, FillStyle::SolidColor(color));
+        }
+    }
+
+    fn push_triangle(&mut self, [a, b, c]: [Pos; 3], color: Color) {
+        // skip empty ones (when the adjacent edge is missing)
+        if (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y) != 0. {
+            self.builder.push_triangle(a, b, c, color);
+        }
+    }
+}
+
+impl BorderBox {
+    fn is_rounded(&self) -> bool {
+        self.radii.iter().any(|r| *r > 0.)
+    }
+
+    fn width(&self, side: Side) -> f32 {
+        self.widths[side as usize]
+    }
+
+    // straight part of the edge, between the corners (and between `from` & `to` fractions of its thickness)
+    // corners thicker than their radius are square inside, so they also cover the rest of the width
+    fn straight(&self, side: Side, from: f32, to: f32) -> Bounds {
+        let Bounds { a, b } = self.bounds;
+        let [tl, tr, br, bl] = self.radii;
+        let [t, r, bt, l] = self.widths;
+        let w = self.width(side);
+        let p = |x, y| Pos { x, y };
+
+        match side {
+            Side::Top => Bounds {
+                a: p(a.x + tl.max(l), a.y + w * from),
+                b: p(b.x - tr.max(r), a.y + w * to),
+            },
+            Side::Right => Bounds {
+                a: p(b.x - w * to, a.y + tr.max(t)),
+                b: p(b.x - w * from, b.y - br.max(bt)),
+            },
+            Side::Bottom => Bounds {
+                a: p(a.x + bl.max(l), b.y - w * to),
+                b: p(b.x - br.max(r), b.y - w * from),
+            },
+            Side::Left => Bounds {
+                a: p(a.x + w * from, a.y + tl.max(t)),
+                b: p(a.x + w * to, b.y - bl.max(bt)),
+            },
+        }
+    }
+
+    // border edge at `f` of the thickness (from the outer side), going through the i-th corner,
+    // from where the straight part of the vertical edge ends to where the horizontal one starts
+    // (the inner curve is elliptic if the widths differ, and square if it's thicker than the radius, same as in CSS)
+    fn corner_curve(&self, i: usize, f: f32) -> Vec<Pos> {
+        let radius = self.radii[i];
+        let (vertical, horizontal) = CORNER_SIDES[i];
+        let (wx, wy) = (self.width(vertical), self.width(horizontal));
+        let (x, y) = (wx * f, wy * f);
+        let (rx, ry) = ((radius - x).max(0.), (radius - y).max(0.));
+
+        // same for every `f`, so that the curves can be joined with a strip
+        let n = (radius * FRAC_PI_2 / ARC_STEP).ceil().max(1.) as usize * 2;
+
+        let mut curve = vec![self.corner_point(i, x, radius.max(wy))];
+        curve.extend((0..=n).map(|k| {
+            let angle = FRAC_PI_2 * k as f32 / n as f32;
+            self.corner_point(i, x + rx * (1. - angle.cos()), y + ry * (1. - angle.sin()))
+        }));
+        curve.push(self.corner_point(i, radius.max(wx), y));
+
+        curve
+    }
+
+    // half of the corner curve, next to the `side`
+    fn corner_half(&self, i: usize, side: Side, f: f32) -> Vec<Pos> {
+        let mut curve = self.corner_curve(i, f);
+        let middle = curve.len() / 2;
+
+        if matches!(side, Side::Top | Side::Bottom) {
+            curve.split_off(middle)
+        } else {
+            curve.truncate(middle + 1);
+            curve
+        }
+    }
+
+    // centers of the dots in the half of the corner next to the `side`
+    // (the one in the middle, or the only one if there's no room for the arc, belongs to the top/bottom edge)
+    fn corner_dots(&self, i: usize, side: Side, size: f32) -> Vec<Pos> {
+        let radius = self.radii[i];
+        let (vertical, horizontal) = CORNER_SIDES[i];
+        let (x, y) = (self.width(vertical) / 2., self.width(horizontal) / 2.);
+        let (rx, ry) = ((radius - x).max(0.), (radius - y).max(0.));
+        let point = |angle: f32| self.corner_point(i, x + rx * (1. - angle.cos()), y + ry * (1. - angle.sin()));
+        let is_horizontal = side == horizontal;
+
+        // close enough for the dot spacing
+        let len = FRAC_PI_2 * (rx + ry) / 2.;
+
+        if len <= 0. {
+            return if is_horizontal { vec![point(0.)] } else { Vec::new() };
+        }
+
+        dashes(len, size)
+            .iter()
+            .map(|(a, b)| (a + b) / 2. / len * FRAC_PI_2)
+            .filter(|angle| (*angle >= FRAC_PI_4) == is_horizontal)
+            .map(point)
+            .collect()
+    }
+
+    // `x` & `y` are distances from the i-th corner of the bounds, towards the inside
+    fn corner_point(&self, i: usize, x: f32, y: f32) -> Pos {
+        let Bounds { a, b } = self.bounds;
+        let (sx, sy) = CORNER_DIRS[i];
+
+        Pos {
+            x: if sx < 0. { a.x + x } else { b.x - x },
+            y: if sy < 0. { a.y + y } else { b.y - y },
+        }
+    }
+}
+
+// corners at the start & end of the edge
+fn side_corners(side: Side) -> [usize; 2] {
+    let i = side as usize;
+    [i, (i + 1) % 4]
+}
+
+fn dot(center: Pos, size: f32) -> Bounds {
+    Bounds {
+        a: Pos {
+            x: center.x - size / 2.,
+            y: center.y - size / 2.,
+        },
+        b: Pos {
+            x: center.x + size / 2.,
+            y: center.y + size / 2.,
+        },
+    }
+}
+
+// straight part of the rounded outline edge, `from` & `to` are distances from the `inner` bounds
+fn straight(inner: Bounds, [tl, tr, br, bl]: [f32; 4], side: Side, from: f32, to: f32) -> Bounds {
+    let Bounds { a, b } = inner;
+    let p = |x, y| Pos { x, y };
+
+    match side {
+        Side::Top => Bounds {
+            a: p(a.x + tl, a.y - to),
+            b: p(b.x - tr, a.y - from),
+        },
+        Side::Right => Bounds {
+            a: p(b.x + from, a.y + tr),
+            b: p(b.x + to, b.y - br),
+        },
+        Side::Bottom => Bounds {
+            a: p(a.x + bl, b.y + from),
+            b: p(b.x - br, b.y + to),
+        },
+        Side::Left => Bounds {
+            a: p(a.x - to, a.y + tl),
+            b: p(a.x - from, b.y - bl),
+        },
+    }
+}
+
+// (center, start angle) for top-left, top-right, bottom-right & bottom-left corner
+fn corners(inner: Bounds, [tl, tr, br, bl]: [f32; 4]) -> [(Pos, f32); 4] {
+    let Bounds { a, b } = inner;
+    let p = |x, y| Pos { x, y };
+
+    [
+        (p(a.x + tl, a.y + tl), PI),
+        (p(b.x - tr, a.y + tr), PI + FRAC_PI_2),
+        (p(b.x - br, b.y - br), 0.),
+        (p(a.x + bl, b.y - bl), FRAC_PI_2),
+    ]
+}
+
+fn arc_point(center: Pos, angle: f32, r: f32) -> Pos {
+    Pos {
+        x: center.x + angle.cos() * r,
+        y: center.y + angle.sin() * r,
+    }
+}
+
+// radii are scaled down if they don't fit (same as in CSS)
+pub(super) fn fit_radii(bounds: Bounds, r: &BorderRadius) -> [f32; 4] {
+    let radii = [r.top_left, r.top_right, r.bottom_right, r.bottom_left].map(|r| r.max(0.));
+    let [tl, tr, br, bl] = radii;
+    let (w, h) = (bounds.width().max(0.), bounds.height().max(0.));
+
+    let fit = |len: f32, sum: f32| if sum > 0. { len / sum } else { 1. };
+    let f = fit(w, tl + tr).min(fit(w, bl + br)).min(fit(h, tl + bl)).min(fit(h, tr + br));
+
+    if f < 1. {
+        radii.map(|r| r * f)
+    } else {
+        radii
+    }
+}
+
+// `from` & `to` are fractions of the thickness, starting from the outer side
+fn trapezoid(bounds: Bounds, [t, r, b, l]: [f32; 4], side: Side, from: f32, to: f32) -> Trapezoid {
+    let inset = |k: f32| Bounds {
+        a: Pos {
+            x: bounds.a.x + l * k,
+            y: bounds.a.y + t * k,
+        },
+        b: Pos {
+            x: bounds.b.x - r * k,
+            y: bounds.b.y - b * k,
+        },
+    };
+    let (o, i) = (inset(from), inset(to));
+    let p = |x, y| Pos { x, y };
+
+    match side {
+        Side::Top => Trapezoid {
+            rect: Bounds {
+                a: p(i.a.x, o.a.y),
+                b: p(i.b.x, i.a.y),
+            },
+            start: [p(o.a.x, o.a.y), p(i.a.x, o.a.y), p(i.a.x, i.a.y)],
+            end: [p(i.b.x, o.a.y), p(o.b.x, o.a.y), p(i.b.x, i.a.y)],
+        },
+        Side::Bottom => Trapezoid {
+            rect: Bounds {
+                a: p(i.a.x, i.b.y),
+                b: p(i.b.x, o.b.y),
+            },
+            start: [p(o.a.x, o.b.y), p(i.a.x, i.b.y), p(i.a.x, o.b.y)],
+            end: [p(i.b.x, o.b.y), p(i.b.x, i.b.y), p(o.b.x, o.b.y)],
+        },
+        Side::Left => Trapezoid {
+            rect: Bounds {
+                a: p(o.a.x, i.a.y),
+                b: p(i.a.x, i.b.y),
+            },
+            start: [p(o.a.x, o.a.y), p(i.a.x, i.a.y), p(o.a.x, i.a.y)],
+            end: [p(o.a.x, i.b.y), p(i.a.x, i.b.y), p(o.a.x, o.b.y)],
+        },
+        Side::Right => Trapezoid {
+            rect: Bounds {
+                a: p(i.b.x, i.a.y),
+                b: p(o.b.x, i.b.y),
+            },
+            start: [p(o.b.x, o.a.y), p(o.b.x, i.a.y), p(i.b.x, i.a.y)],
+            end: [p(i.b.x, i.b.y), p(o.b.x, i.b.y), p(o.b.x, o.b.y)],
+        },
+    }
+}
+
+// (start, end) of equally sized dashes & gaps, so that both ends start with a dash
+// (so the corners are always covered)
+fn dashes(len: f32, dash: f32) -> Vec<(f32, f32)> {
+    if dash <= 0. || len <= 0. {
+        return Vec::new();
+    }
+
+    let count = ((len / dash + 1.) / 2.).round().max(1.);
+    let dash = len / (count * 2. - 1.);
+
+    (0..count as usize).map(|i| (i as f32 * dash * 2., i as f32 * dash * 2. + dash)).collect()
+}
+
+fn length(bounds: Bounds, side: Side) -> f32 {
+    let (a, b) = along(bounds, side);
+    b - a
+}
+
+// start & end of the bounds, along the edge
+fn along(bounds: Bounds, side: Side) -> (f32, f32) {
+    match side {
+        Side::Top | Side::Bottom => (bounds.a.x, bounds.b.x),
+        Side::Left | Side::Right => (bounds.a.y, bounds.b.y),
+    }
+}
+
+// part of the edge, between `start` & `end` (absolute coords along the edge)
+fn segment(bounds: Bounds, side: Side, start: f32, end: f32) -> Bounds {
+    let Bounds { a, b } = bounds;
+
+    match side {
+        Side::Top | Side::Bottom => Bounds {
+            a: Pos { x: start, y: a.y },
+            b: Pos { x: end, y: b.y },
+        },
+        Side::Left | Side::Right => Bounds {
+            a: Pos { x: a.x, y: start },
+            b: Pos { x: b.x, y: end },
+        },
+    }
+}
+
+fn dark(c: Color) -> Color {
+    let ch = |v: u8| (v as u32 * 2 / 3) as u8;
+
+    Color {
+        r: ch(c.r),
+        g: ch(c.g),
+        b: ch(c.b),
+        a: c.a,
+    }
+}
+
+fn light(c: Color) -> Color {
+    let ch = |v: u8| (v as u32 + (255 - v as u32) / 3) as u8;
+
+    Color {
+        r: ch(c.r),
+        g: ch(c.g),
+        b: ch(c.b),
+        a: c.a,
+    }
+}
+
+// single-channel sdf (in all 3 channels so it's also valid msdf)
+pub fn gen_circle() -> Box<[u8]> {
+    let r = CIRCLE_SIZE as f32 / 2.;
+    let mut data = Vec::new();
+
+    for y in 0..CIRCLE_SIZE {
+        for x in 0..CIRCLE_SIZE {
+            let (dx, dy) = (x as f32 + 0.5 - r, y as f32 + 0.5 - r);
+            let v = (0.5 + (r - (dx * dx + dy * dy).sqrt()) / CIRCLE_RANGE).clamp(0., 1.);
+            let v = (v * 255.) as u8;
+
+            data.extend(&[v, v, v, 0xFF]);
+        }
+    }
+
+    data.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dash_distribution() {
+        assert_eq!(dashes(5., 1.), vec![(0., 1.), (2., 3.), (4., 5.)]);
+
+        // shrunk a bit
+        assert_eq!(dashes(9., 4.), vec![(0., 3.), (6., 9.)]);
+
+        // too short
+        assert_eq!(dashes(2., 3.), vec![(0., 2.)]);
+    }
+}
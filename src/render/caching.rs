@@ -0,0 +1,247 @@
+// layer caching
+//
+// x every stacking context has its own layer
+//   - root context renders into the root layer (absolute coords)
+//   - others are relative to their top-left corner & referenced with `push_layer()`
+//     so they can move without being rebuilt
+// x setters mark containers dirty (images mark all containers using them)
+// x layer is rebuilt only if any of its containers is dirty, their bounds have changed,
+//   or its children contexts have changed/moved
+// - layers of containers which are not contexts anymore are kept around (TODO: free)
+// x damage is collected during the same walk (see damage.rs)
+//   - inflated by the filters of all the ancestors (they can blur the change around)
+// x culling
+//   - cull rect is the viewport, narrowed by clips & masks
+//     (not by overflow until it's actually clipped)
+//   - and inflated by filters, they can pull in pixels from outside
+//   - flow subtrees are skipped if their extent (outlines, shadows, ...) is outside of it
+//   - contexts only by their own visual bounds (they are still walked for damage
+//     but their layers are not rebuilt until they are visible again)
+
+use super::backend::RenderBackend;
+use super::stacking::StackedChild;
+use super::{masking, BackgroundImage, Child, ContainerId, ImageId, MaskTexture, RenderError, UiState};
+use crate::commons::{Bounds, Pos};
+use std::ops::Index;
+
+const EVERYTHING: Bounds = Bounds {
+    a: Pos {
+        x: f32::NEG_INFINITY,
+        y: f32::NEG_INFINITY,
+    },
+    b: Pos {
+        x: f32::INFINITY,
+        y: f32::INFINITY,
+    },
+};
+
+// where (and how) the context is
+#[derive(Clone, Copy)]
+struct Frame {
+    // of the context itself (absolute for the root, local for others)
+    bounds: Bounds,
+    // absolute
+    origin: Pos,
+    // relative to the context
+    cull: Bounds,
+    // of the ancestors' filters (for damage)
+    reach: f32,
+}
+
+impl<RB: RenderBackend, BK: Copy> UiState<RB, BK> {
+    // contexts which need to be rebuilt, with the bounds & cull rect they should be rendered with
+    // (parents go first, missing layers are created)
+    pub(super) fn update_layers(
+        &mut self,
+        backend: &mut RB,
+        root: ContainerId,
+        bounds: Bounds,
+        bounds_map: &impl Index<BK, Output = Bounds>,
+    ) -> Vec<(ContainerId, Bounds, Bounds)> {
+        let mut res = Vec::new();
+        let cull = self.viewport.unwrap_or(EVERYTHING);
+        let root_changed = self.root != Some(root);
+
+        self.root = Some(root);
+        // root layer is not the container's own layer
+        self.layers_valid[root.0] = false;
+
+        // everything what was there before
+        let prev_extent = std::mem::replace(&mut self.extent, Bounds::ZERO);
+        self.backdrops.clear();
+
+        if root_changed {
+            self.pending_damage.push(prev_extent);
+        }
+
+        let frame = Frame {
+            bounds,
+            origin: Pos::ZERO,
+            cull,
+            reach: 0.,
+        };
+        self.update_context(backend, root, frame, bounds_map, root_changed, false, true, &mut res);
+        self.damage_backdrops();
+
+        res
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn update_context(
+        &mut self,
+        backend: &mut RB,
+        container: ContainerId,
+        Frame { bounds, origin, cull, reach }: Frame,
+        bounds_map: &impl Index<BK, Output = Bounds>,
+        force: bool,
+        parent_dirty: bool,
+        visible: bool,
+        res: &mut Vec<(ContainerId, Bounds, Bounds)>,
+    ) {
+        let mut changed = self.check_bounds(container, bounds) | force;
+        changed |= std::mem::replace(&mut self.last_culls[container.0], cull) != cull;
+        // whole subtree is affected by filters, opacity, ...
+        let subtree_dirty = self.dirty[container.0] || parent_dirty || force;
+        changed |= self.update_flow(container, bounds, origin, reach, bounds_map, parent_dirty || force);
+
+        let (mut stacked, rest) = self.stacked_children(container, bounds, bounds_map);
+        stacked.extend(rest);
+        changed |= self.stacked[container.0] != stacked;
+
+        if !visible {
+            // rebuild once it's visible again
+            self.layers_valid[container.0] = false;
+        } else if changed {
+            res.push((container, bounds, cull));
+        }
+
+        let inner_cull = self.narrow_cull(container, bounds, cull);
+        let inner_reach = reach + self.filter_reach(container);
+
+        for ch in &stacked {
+            let local = Bounds {
+                a: Pos::ZERO,
+                b: Pos {
+                    x: ch.bounds.width(),
+                    y: ch.bounds.height(),
+                },
+            };
+
+            if self.layers[ch.container.0].is_none() {
+                self.layers[ch.container.0] = Some(backend.create_layer());
+            }
+
+            let force = !self.layers_valid[ch.container.0];
+            self.layers_valid[ch.container.0] = true;
+            let frame = Frame {
+                bounds: local,
+                origin: origin.translate(ch.bounds.a),
+                cull: inner_cull.translate(Pos {
+                    x: -ch.bounds.a.x,
+                    y: -ch.bounds.a.y,
+                }),
+                reach: inner_reach,
+            };
+            let visible = visible && self.is_visible(ch, inner_cull);
+            self.update_context(backend, ch.container, frame, bounds_map, force, subtree_dirty, visible, res);
+        }
+
+        self.stacked[container.0] = stacked;
+    }
+
+    // the non-context part of the subtree, returns true if anything has changed
+    // (`bounds` are relative to the context, `origin` is where the context is)
+    fn update_flow(
+        &mut self,
+        container: ContainerId,
+        bounds: Bounds,
+        origin: Pos,
+        reach: f32,
+        bounds_map: &impl Index<BK, Output = Bounds>,
+        parent_dirty: bool,
+    ) -> bool {
+        let mut changed = std::mem::replace(&mut self.dirty[container.0], false);
+        let dirty = changed || parent_dirty;
+
+        self.damage_container(container, bounds.translate(origin), reach, dirty);
+        let inner_reach = reach + self.filter_reach(container);
+        let mut extent = self.visual_bounds(container, bounds);
+
+        for i in 0..self.children[container.0].len() {
+            match self.children[container.0][i] {
+                Child::Container(ch) if self.creates_stacking_context(ch) => {}
+                Child::Container(ch) => {
+                    let ch_bounds = bounds_map[self.bounds_keys[ch.0]].translate(bounds.a);
+
+                    self.layers_valid[ch.0] = false;
+                    changed |= self.check_bounds(ch, ch_bounds);
+                    changed |= self.update_flow(ch, ch_bounds, origin, inner_reach, bounds_map, dirty);
+                    extent = extent.union(&self.flow_extents[ch.0]);
+                }
+                Child::Text(text) => {
+                    let text_bounds = bounds_map[self.text_bounds_keys[text.0]].translate(bounds.a);
+                    changed |= std::mem::replace(&mut self.text_last_bounds[text.0], text_bounds) != text_bounds;
+                    self.damage_text(text, text_bounds.translate(origin), inner_reach, dirty);
+                    extent = extent.union(&text_bounds);
+                }
+            }
+        }
+
+        self.flow_extents[container.0] = extent;
+
+        changed
+    }
+
+    // anything drawn outside of this is not visible
+    pub(super) fn narrow_cull(&self, container: ContainerId, bounds: Bounds, cull: Bounds) -> Bounds {
+        let mut cull = cull;
+
+        if self.masks[container.0].is_some() {
+            cull = cull.intersect(&bounds);
+        }
+
+        if let Some(clip) = &self.clip_paths[container.0] {
+            cull = cull.intersect(&masking::bounding_box(&masking::clip_path(clip, bounds)));
+        }
+
+        // (clip & mask are applied after filters)
+        if !cull.is_empty() {
+            cull = cull.inflate_uniform(self.filter_reach(container));
+        }
+
+        cull
+    }
+
+    pub(super) fn is_visible(&self, stacked: &StackedChild, cull: Bounds) -> bool {
+        self.visual_bounds(stacked.container, stacked.bounds).intersects(&cull)
+    }
+
+    fn check_bounds(&mut self, container: ContainerId, bounds: Bounds) -> bool {
+        std::mem::replace(&mut self.last_bounds[container.0], bounds) != bounds
+    }
+
+    // (setters go through this so it's also where the handle is checked)
+    pub(super) fn mark_dirty(&mut self, container: ContainerId) -> Result<(), RenderError> {
+        self.check_container(container)?;
+        self.dirty[container.0] = true;
+
+        Ok(())
+    }
+
+    // texture of the image has been replaced (or it should be sampled differently)
+    pub(super) fn mark_image_users_dirty(&mut self, image: ImageId) {
+        for i in 0..self.dirty.len() {
+            self.dirty[i] |= self.uses_image(i, image);
+        }
+    }
+
+    pub(super) fn uses_image(&self, container: usize, image: ImageId) -> bool {
+        let in_background = self.background_images[container].iter().any(|b| match b {
+            BackgroundImage::Image { image: img, .. } | BackgroundImage::NineSlice { image: img, .. } => *img == image,
+            _ => false,
+        });
+        let in_mask = matches!(self.masks[container], Some(MaskTexture::Image(img)) if img == image);
+
+        in_background || in_mask
+    }
+}
@@ -0,0 +1,148 @@
+// damage tracking
+//
+// x every container/text remembers where it was drawn (absolute, including outlines, shadows, ...)
+// x changed ones damage both the old & the new area
+//   - dirty contexts damage their whole subtree (filters, opacity, ...)
+//   - removed subtrees & updated textures damage their old area right away
+// x ancestors' filters spread the damage (see caching.rs)
+// x backdrop-filters are damaged (with all they sample from) if anything there has changed
+// x overlapping rects are merged, backend only clears & redraws those
+
+use super::backend::RenderBackend;
+use super::{Child, ContainerId, Filter, ImageId, TextId, UiState};
+use crate::commons::Bounds;
+
+impl<RB: RenderBackend, BK: Copy> UiState<RB, BK> {
+    pub(super) fn damage_container(&mut self, container: ContainerId, bounds: Bounds, reach: f32, dirty: bool) {
+        let visual = self.visual_bounds(container, bounds).inflate_uniform(reach);
+        let backdrop_filters = &self.backdrop_filters[container.0];
+
+        if !backdrop_filters.is_empty() {
+            self.backdrops
+                .push((bounds.inflate_uniform(reach), bounds.inflate_uniform(filters_reach(backdrop_filters))));
+        }
+
+        let prev = std::mem::replace(&mut self.last_visual_bounds[container.0], visual);
+
+        if dirty || prev != visual {
+            self.pending_damage.push(prev);
+            self.pending_damage.push(visual);
+        }
+
+        self.extend(visual);
+    }
+
+    pub(super) fn damage_text(&mut self, text: TextId, bounds: Bounds, reach: f32, dirty: bool) {
+        let bounds = bounds.inflate_uniform(reach);
+        let prev = std::mem::replace(&mut self.text_last_visual_bounds[text.0], bounds);
+
+        if dirty || prev != bounds {
+            self.pending_damage.push(prev);
+            self.pending_damage.push(bounds);
+        }
+
+        self.extend(bounds);
+    }
+
+    // wherever the subtree was drawn the last time
+    pub(super) fn damage_subtree(&mut self, container: ContainerId) {
+        self.pending_damage.push(self.last_visual_bounds[container.0]);
+
+        for i in 0..self.children[container.0].len() {
+            match self.children[container.0][i] {
+                Child::Container(ch) => self.damage_subtree(ch),
+                Child::Text(text) => self.pending_damage.push(self.text_last_visual_bounds[text.0]),
+            }
+        }
+    }
+
+    // pixels have changed but the texture is the same
+    pub(super) fn damage_image_users(&mut self, image: ImageId) {
+        for i in 0..self.last_visual_bounds.len() {
+            if self.uses_image(i, image) {
+                self.pending_damage.push(self.last_visual_bounds[i]);
+            }
+        }
+    }
+
+    // (one can be under another, so it's repeated until nothing is added)
+    pub(super) fn damage_backdrops(&mut self) {
+        let mut backdrops = std::mem::take(&mut self.backdrops);
+
+        loop {
+            let len = backdrops.len();
+
+            backdrops.retain(|(area, sample)| {
+                if self.pending_damage.iter().any(|d| d.intersects(sample)) {
+                    // whole sample too, otherwise it'd be blurred with what was drawn over it the last time
+                    self.pending_damage.push(*area);
+                    self.pending_damage.push(*sample);
+                    return false;
+                }
+
+                true
+            });
+
+            if backdrops.len() == len {
+                break;
+            }
+        }
+    }
+
+    // merged rects, resets the damage
+    pub(super) fn take_damage(&mut self) -> Vec<Bounds> {
+        let mut res: Vec<Bounds> = Vec::new();
+
+        for mut rect in self.pending_damage.drain(..).filter(|r| !r.is_empty()) {
+            while let Some(i) = res.iter().position(|r| r.intersects(&rect)) {
+                rect = rect.union(&res.swap_remove(i));
+            }
+
+            res.push(rect);
+        }
+
+        res
+    }
+
+    // bounds + everything what can be drawn outside of them
+    pub(super) fn visual_bounds(&self, container: ContainerId, bounds: Bounds) -> Bounds {
+        let mut n: f32 = 0.;
+
+        if let Some(o) = &self.outlines[container.0] {
+            n = n.max(o.width + o.offset);
+        }
+
+        for s in &self.outline_shadows[container.0] {
+            n = n.max(s.offset.x.abs().max(s.offset.y.abs()) + s.spread + s.blur);
+        }
+
+        n += self.filter_reach(container);
+
+        bounds.inflate_uniform(n.max(0.))
+    }
+
+    // how far can filters move/blur the pixels (of the whole subtree)
+    pub(super) fn filter_reach(&self, container: ContainerId) -> f32 {
+        filters_reach(&self.filters[container.0])
+    }
+
+    fn extend(&mut self, bounds: Bounds) {
+        if self.extent.is_empty() {
+            self.extent = bounds;
+        } else {
+            self.extent = self.extent.union(&bounds);
+        }
+    }
+}
+
+// filters are applied one after another
+pub(super) fn filters_reach(filters: &[Filter]) -> f32 {
+    filters
+        .iter()
+        .map(|f| match *f {
+            Filter::Blur(sigma) => sigma * 3.,
+            Filter::DropShadow { offset, blur, .. } => blur * 3. + offset.x.abs().max(offset.y.abs()),
+            _ => 0.,
+        })
+        .sum()
+}
@@ -0,0 +1,674 @@
+// image decoding
+// - format is detected from the magic bytes
+// - everything is converted to non-premultiplied RGBA8
+//   (which is what `create_image()` accepts)
+
+use std::fmt::{self, Display, Formatter};
+use std::io::Cursor;
+use std::time::Duration;
+
+pub struct DecodedImage {
+    pub width: i32,
+    pub height: i32,
+    pub data: Box<[u8]>,
+}
+
+// frames are not composited, it has to be done during playback
+pub struct AnimatedImage {
+    pub width: i32,
+    pub height: i32,
+    pub frames: Vec<Frame>,
+    // how many times it should be played (None = forever)
+    pub plays: Option<u32>,
+}
+
+// (sub)frame which should be drawn at `x, y` of the canvas
+pub struct Frame {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub data: Box<[u8]>,
+    pub delay: Duration,
+    pub disposal: Disposal,
+    pub blend: Blend,
+}
+
+// what to do with the frame area before the next one is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Disposal {
+    None,
+    Background,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Blend {
+    Source,
+    Over,
+}
+
+#[derive(Debug)]
+pub enum DecodingError {
+    UnknownFormat,
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    Gif(gif::DecodingError),
+    WebP(image_webp::DecodingError),
+    NoFrames,
+    // APNG frame without fcTL
+    MissingFrameControl,
+    // empty or too big (RGBA data has to be addressable with i32)
+    InvalidSize { width: u32, height: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => Some(Self::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(Self::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7', b'a', ..] | [b'G', b'I', b'F', b'8', b'9', b'a', ..] => Some(Self::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(Self::WebP),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode_image(bytes: &[u8]) -> Result<DecodedImage, DecodingError> {
+    match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Png) => decode_png(bytes),
+        Some(ImageFormat::Jpeg) => decode_jpeg(bytes),
+        Some(ImageFormat::Gif) => decode_gif(bytes),
+        Some(ImageFormat::WebP) => decode_webp(bytes),
+        None => Err(DecodingError::UnknownFormat),
+    }
+}
+
+// all frames (static images will have just one)
+pub fn decode_animation(bytes: &[u8]) -> Result<AnimatedImage, DecodingError> {
+    match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Png) => decode_apng(bytes),
+        Some(ImageFormat::Gif) => decode_gif_frames(bytes),
+        Some(ImageFormat::WebP) => decode_webp_frames(bytes),
+        _ => decode_image(bytes).map(AnimatedImage::from),
+    }
+}
+
+impl From<DecodedImage> for AnimatedImage {
+    fn from(img: DecodedImage) -> Self {
+        Self {
+            width: img.width,
+            height: img.height,
+            frames: vec![Frame {
+                x: 0,
+                y: 0,
+                width: img.width,
+                height: img.height,
+                data: img.data,
+                delay: Duration::from_secs(0),
+                disposal: Disposal::None,
+                blend: Blend::Source,
+            }],
+            plays: Some(1),
+        }
+    }
+}
+
+fn png_reader(bytes: &[u8]) -> Result<png::Reader<&[u8]>, DecodingError> {
+    let mut decoder = png::Decoder::new(bytes);
+
+    // palette -> rgb(a), tRNS -> alpha, 1/2/4 bits -> 8 bits (16 bits are kept)
+    decoder.set_transformations(png::Transformations::EXPAND);
+
+    let reader = decoder.read_info()?;
+    check_size(reader.info().width, reader.info().height)?;
+
+    Ok(reader)
+}
+
+// next (sub)frame as rgba
+fn png_frame(reader: &mut png::Reader<&[u8]>, buf: &mut [u8]) -> Result<DecodedImage, DecodingError> {
+    let info = reader.next_frame(buf)?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => unreachable!("palette should be expanded"),
+    };
+    let depth = match info.bit_depth {
+        png::BitDepth::Sixteen => Depth::Sixteen(Endian::Big),
+        _ => Depth::Eight,
+    };
+
+    Ok(DecodedImage {
+        width: info.width as i32,
+        height: info.height as i32,
+        data: to_rgba(&buf[..info.buffer_size()], channels, depth),
+    })
+}
+
+fn decode_png(bytes: &[u8]) -> Result<DecodedImage, DecodingError> {
+    let mut reader = png_reader(bytes)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+
+    png_frame(&mut reader, &mut buf)
+}
+
+fn decode_apng(bytes: &[u8]) -> Result<AnimatedImage, DecodingError> {
+    let mut reader = png_reader(bytes)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+
+    let actl = match reader.info().animation_control {
+        Some(actl) => actl,
+        None => return png_frame(&mut reader, &mut buf).map(AnimatedImage::from),
+    };
+
+    // default image is only part of the animation if it has fcTL
+    if reader.info().frame_control.is_none() {
+        png_frame(&mut reader, &mut buf)?;
+    }
+
+    let (width, height) = (reader.info().width as i32, reader.info().height as i32);
+    let mut frames = Vec::new();
+
+    for _ in 0..actl.num_frames {
+        let DecodedImage { width, height, data } = png_frame(&mut reader, &mut buf)?;
+        let fctl = reader.info().frame_control.ok_or(DecodingError::MissingFrameControl)?;
+        let den = if fctl.delay_den == 0 { 100 } else { fctl.delay_den };
+
+        frames.push(Frame {
+            x: fctl.x_offset as i32,
+            y: fctl.y_offset as i32,
+            width,
+            height,
+            data,
+            delay: Duration::from_millis(fctl.delay_num as u64 * 1000 / den as u64),
+            disposal: match fctl.dispose_op {
+                png::DisposeOp::None => Disposal::None,
+                png::DisposeOp::Background => Disposal::Background,
+                png::DisposeOp::Previous => Disposal::Previous,
+            },
+            blend: match fctl.blend_op {
+                png::BlendOp::Source => Blend::Source,
+                png::BlendOp::Over => Blend::Over,
+            },
+        });
+    }
+
+    if frames.is_empty() {
+        return Err(DecodingError::NoFrames);
+    }
+
+    Ok(AnimatedImage {
+        width,
+        height,
+        frames,
+        plays: if actl.num_plays == 0 { None } else { Some(actl.num_plays) },
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<DecodedImage, DecodingError> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+
+    // before anything gets allocated
+    decoder.read_info()?;
+    let info = decoder.info().expect("info should be available after read_info");
+    check_size(info.width as u32, info.height as u32)?;
+
+    let pixels = decoder.decode()?;
+
+    let data = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => to_rgba(&pixels, 1, Depth::Eight),
+        jpeg_decoder::PixelFormat::L16 => to_rgba(&pixels, 1, Depth::Sixteen(Endian::Native)),
+        jpeg_decoder::PixelFormat::RGB24 => to_rgba(&pixels, 3, Depth::Eight),
+        jpeg_decoder::PixelFormat::CMYK32 => cmyk_to_rgba(&pixels),
+    };
+
+    Ok(DecodedImage {
+        width: info.width as i32,
+        height: info.height as i32,
+        data,
+    })
+}
+
+fn gif_decoder(bytes: &[u8]) -> Result<gif::Decoder<&[u8]>, DecodingError> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+
+    Ok(options.read_info(bytes)?)
+}
+
+// first frame only
+fn decode_gif(bytes: &[u8]) -> Result<DecodedImage, DecodingError> {
+    let mut decoder = gif_decoder(bytes)?;
+    check_size(decoder.width() as u32, decoder.height() as u32)?;
+
+    let (width, height) = (decoder.width() as i32, decoder.height() as i32);
+    let frame = decoder.read_next_frame()?.ok_or(DecodingError::NoFrames)?;
+    check_size(frame.width as u32, frame.height as u32)?;
+
+    // frame can be smaller than the logical screen
+    let mut data = vec![0; width as usize * height as usize * 4].into_boxed_slice();
+    blit(&mut data, width, frame.left as i32, frame.top as i32, frame.width as i32, &frame.buffer);
+
+    Ok(DecodedImage { width, height, data })
+}
+
+fn decode_gif_frames(bytes: &[u8]) -> Result<AnimatedImage, DecodingError> {
+    let mut decoder = gif_decoder(bytes)?;
+    check_size(decoder.width() as u32, decoder.height() as u32)?;
+
+    let (width, height) = (decoder.width() as i32, decoder.height() as i32);
+    let mut frames = Vec::new();
+
+    while let Some(frame) = decoder.read_next_frame()? {
+        check_size(frame.width as u32, frame.height as u32)?;
+
+        frames.push(Frame {
+            x: frame.left as i32,
+            y: frame.top as i32,
+            width: frame.width as i32,
+            height: frame.height as i32,
+            data: frame.buffer.to_vec().into_boxed_slice(),
+            // in 10ms units
+            delay: Duration::from_millis(frame.delay as u64 * 10),
+            disposal: match frame.dispose {
+                gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Disposal::None,
+                gif::DisposalMethod::Background => Disposal::Background,
+                gif::DisposalMethod::Previous => Disposal::Previous,
+            },
+            // transparent index is already alpha = 0
+            blend: Blend::Over,
+        });
+    }
+
+    if frames.is_empty() {
+        return Err(DecodingError::NoFrames);
+    }
+
+    Ok(AnimatedImage {
+        width,
+        height,
+        frames,
+        // number of repetitions, not plays
+        plays: match decoder.repeat() {
+            gif::Repeat::Infinite => None,
+            gif::Repeat::Finite(n) => Some(n as u32 + 1),
+        },
+    })
+}
+
+fn decode_webp(bytes: &[u8]) -> Result<DecodedImage, DecodingError> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(bytes))?;
+    let (width, height) = decoder.dimensions();
+    check_size(width, height)?;
+
+    let channels = if decoder.has_alpha() { 4 } else { 3 };
+    let mut buf = vec![0; decoder.output_buffer_size().ok_or(image_webp::DecodingError::ImageTooLarge)?];
+
+    decoder.read_image(&mut buf)?;
+
+    Ok(DecodedImage {
+        width: width as i32,
+        height: height as i32,
+        data: to_rgba(&buf, channels, Depth::Eight),
+    })
+}
+
+// webp frames are already composited (so they all cover the whole canvas)
+fn decode_webp_frames(bytes: &[u8]) -> Result<AnimatedImage, DecodingError> {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(bytes))?;
+
+    if !decoder.is_animated() {
+        return decode_webp(bytes).map(AnimatedImage::from);
+    }
+
+    let (width, height) = decoder.dimensions();
+    check_size(width, height)?;
+
+    let channels = if decoder.has_alpha() { 4 } else { 3 };
+    let mut buf = vec![0; decoder.output_buffer_size().ok_or(image_webp::DecodingError::ImageTooLarge)?];
+    let mut frames = Vec::new();
+
+    for _ in 0..decoder.num_frames() {
+        let millis = decoder.read_frame(&mut buf)?;
+
+        frames.push(Frame {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+            data: to_rgba(&buf, channels, Depth::Eight),
+            delay: Duration::from_millis(millis as u64),
+            disposal: Disposal::None,
+            blend: Blend::Source,
+        });
+    }
+
+    if frames.is_empty() {
+        return Err(DecodingError::NoFrames);
+    }
+
+    Ok(AnimatedImage {
+        width: width as i32,
+        height: height as i32,
+        frames,
+        plays: match decoder.loop_count() {
+            image_webp::LoopCount::Forever => None,
+            image_webp::LoopCount::Times(n) => Some(n.get() as u32),
+        },
+    })
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), DecodingError> {
+    if width == 0 || height == 0 || width as u64 * height as u64 * 4 > i32::MAX as u64 {
+        return Err(DecodingError::InvalidSize { width, height });
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Endian {
+    Big,
+    Native,
+}
+
+#[derive(Clone, Copy)]
+enum Depth {
+    Eight,
+    Sixteen(Endian),
+}
+
+// gray, gray + alpha, rgb, rgba (8/16 bits) -> rgba8
+fn to_rgba(data: &[u8], channels: usize, depth: Depth) -> Box<[u8]> {
+    let sample_size = match depth {
+        Depth::Eight => 1,
+        Depth::Sixteen(_) => 2,
+    };
+
+    let sample = |px: &[u8], i: usize| match depth {
+        Depth::Eight => px[i],
+        Depth::Sixteen(Endian::Big) => px[i * 2],
+        Depth::Sixteen(Endian::Native) => (u16::from_ne_bytes([px[i * 2], px[i * 2 + 1]]) >> 8) as u8,
+    };
+
+    data.chunks_exact(channels * sample_size)
+        .flat_map(|px| match channels {
+            1 => [sample(px, 0), sample(px, 0), sample(px, 0), 0xFF],
+            2 => [sample(px, 0), sample(px, 0), sample(px, 0), sample(px, 1)],
+            3 => [sample(px, 0), sample(px, 1), sample(px, 2), 0xFF],
+            _ => [sample(px, 0), sample(px, 1), sample(px, 2), sample(px, 3)],
+        })
+        .collect()
+}
+
+// jpeg-decoder gives inverted (adobe) cmyk
+fn cmyk_to_rgba(data: &[u8]) -> Box<[u8]> {
+    data.chunks_exact(4)
+        .flat_map(|px| {
+            let k = 255 - px[3] as u16;
+            let ch = |v: u8| ((255 - v as u16) * k / 255) as u8;
+
+            [ch(px[0]), ch(px[1]), ch(px[2]), 0xFF]
+        })
+        .collect()
+}
+
+// copy `src` rgba rect to `x, y` of `dest` (which is `dest_width` wide)
+pub(super) fn blit(dest: &mut [u8], dest_width: i32, x: i32, y: i32, width: i32, src: &[u8]) {
+    if dest_width <= 0 || width <= 0 {
+        return;
+    }
+
+    let dest_height = dest.len() as i32 / 4 / dest_width;
+
+    for (row, line) in src.chunks_exact(width as usize * 4).enumerate() {
+        let dy = y + row as i32;
+
+        // clip to the screen
+        let len = (width.min(dest_width - x).max(0) * 4) as usize;
+
+        if dy >= dest_height || len == 0 {
+            break;
+        }
+
+        let start = ((dy * dest_width + x) * 4) as usize;
+
+        dest[start..start + len].copy_from_slice(&line[..len]);
+    }
+}
+
+impl Display for DecodingError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "unknown image format"),
+            Self::Png(e) => write!(f, "png: {}", e),
+            Self::Jpeg(e) => write!(f, "jpeg: {}", e),
+            Self::Gif(e) => write!(f, "gif: {}", e),
+            Self::WebP(e) => write!(f, "webp: {}", e),
+            Self::NoFrames => write!(f, "image has no frames"),
+            Self::MissingFrameControl => write!(f, "apng frame without fcTL"),
+            Self::InvalidSize { width, height } => write!(f, "invalid size {}x{}", width, height),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+impl From<png::DecodingError> for DecodingError {
+    fn from(e: png::DecodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+impl From<jpeg_decoder::Error> for DecodingError {
+    fn from(e: jpeg_decoder::Error) -> Self {
+        Self::Jpeg(e)
+    }
+}
+
+impl From<gif::DecodingError> for DecodingError {
+    fn from(e: gif::DecodingError) -> Self {
+        Self::Gif(e)
+    }
+}
+
+impl From<image_webp::DecodingError> for DecodingError {
+    fn from(e: image_webp::DecodingError) -> Self {
+        Self::WebP(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(ImageFormat::detect(b"GIF89a..."), Some(ImageFormat::Gif));
+        assert_eq!(ImageFormat::detect(b"RIFF\0\0\0\0WEBPVP8 "), Some(ImageFormat::WebP));
+        assert_eq!(ImageFormat::detect(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::detect(b"<svg"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(decode_image(b"hello"), Err(DecodingError::UnknownFormat)));
+        assert!(matches!(decode_image(b"\x89PNG\r\n\x1a\n\0\0"), Err(DecodingError::Png(_))));
+        assert!(matches!(decode_image(b"GIF89a"), Err(DecodingError::Gif(_))));
+    }
+
+    #[test]
+    fn png_color_types() {
+        // 16-bit gray
+        let img = decode_image(&encode_png(1, 1, png::ColorType::Grayscale, png::BitDepth::Sixteen, &[0x80, 0xFF], None)).unwrap();
+        assert_eq!(&*img.data, &[0x80, 0x80, 0x80, 0xFF]);
+
+        // gray + alpha
+        let img = decode_image(&encode_png(1, 1, png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, &[10, 20], None)).unwrap();
+        assert_eq!(&*img.data, &[10, 10, 10, 20]);
+
+        // palette (2 bits)
+        let img = decode_image(&encode_png(
+            2,
+            1,
+            png::ColorType::Indexed,
+            png::BitDepth::Two,
+            &[0b0001_0000],
+            Some(&[255, 0, 0, 0, 0, 255]),
+        ))
+        .unwrap();
+        assert_eq!((img.width, img.height), (2, 1));
+        assert_eq!(&*img.data, &[255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn gif_frames() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut bytes, 2, 1, &[0, 0, 0, 255, 255, 255]).unwrap();
+            encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+
+            for (left, delay) in &[(0, 5), (1, 20)] {
+                let frame = gif::Frame {
+                    left: *left,
+                    width: 1,
+                    height: 1,
+                    delay: *delay,
+                    dispose: gif::DisposalMethod::Background,
+                    buffer: vec![1].into(),
+                    ..Default::default()
+                };
+                encoder.write_frame(&frame).unwrap();
+            }
+        }
+
+        let anim = decode_animation(&bytes).unwrap();
+
+        assert_eq!((anim.width, anim.height, anim.plays), (2, 1, None));
+        assert_eq!(anim.frames.len(), 2);
+        assert_eq!(anim.frames[1].x, 1);
+        assert_eq!(anim.frames[1].delay, Duration::from_millis(200));
+        assert_eq!(anim.frames[1].disposal, Disposal::Background);
+        assert_eq!(&*anim.frames[1].data, &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn gif_sizes() {
+        let gif = |width, height, frame_width| {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = gif::Encoder::new(&mut bytes, width, height, &[0, 0, 0]).unwrap();
+                let frame = gif::Frame {
+                    width: frame_width,
+                    height: 1,
+                    buffer: vec![0; frame_width as usize].into(),
+                    ..Default::default()
+                };
+                encoder.write_frame(&frame).unwrap();
+            }
+            bytes
+        };
+
+        assert!(matches!(decode_image(&gif(0, 0, 1)), Err(DecodingError::InvalidSize { width: 0, height: 0 })));
+        assert!(matches!(decode_animation(&gif(0, 1, 1)), Err(DecodingError::InvalidSize { .. })));
+        // (gif rejects empty frames itself, but it's checked anyway)
+        assert!(decode_image(&gif(1, 1, 0)).is_err());
+        assert!(decode_animation(&gif(1, 1, 0)).is_err());
+        assert!(matches!(decode_image(&gif(u16::MAX, u16::MAX, 1)), Err(DecodingError::InvalidSize { .. })));
+
+        // smaller frame is fine
+        assert_eq!(decode_image(&gif(2, 1, 1)).unwrap().data.len(), 8);
+    }
+
+    #[test]
+    fn header_sizes() {
+        // (zero sizes are rejected by the decoders themselves, but they're checked anyway)
+        assert!(decode_image(&png_header(0, 1)).is_err());
+        assert!(decode_image(&jpeg_header(1, 0)).is_err());
+        assert!(decode_animation(&png_header(1, 0)).is_err());
+
+        // too big, before anything gets allocated
+        assert!(matches!(decode_image(&png_header(65535, 65535)), Err(DecodingError::InvalidSize { .. })));
+        assert!(matches!(decode_animation(&png_header(65535, 65535)), Err(DecodingError::InvalidSize { .. })));
+        assert!(matches!(
+            decode_image(&jpeg_header(65535, 65535)),
+            Err(DecodingError::InvalidSize { width: 65535, height: 65535 })
+        ));
+        assert!(matches!(decode_image(&webp_header(32768, 32768)), Err(DecodingError::InvalidSize { .. })));
+        assert!(matches!(decode_animation(&webp_header(32768, 32768)), Err(DecodingError::InvalidSize { .. })));
+    }
+
+    // signature + IHDR (rgba8) + IDAT
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let crc = |data: &[u8]| {
+            let mut crc = !0u32;
+
+            for &b in data {
+                crc ^= b as u32;
+
+                for _ in 0..8 {
+                    crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                }
+            }
+
+            !crc
+        };
+
+        let ihdr = [&b"IHDR"[..], &width.to_be_bytes(), &height.to_be_bytes(), &[8, 6, 0, 0, 0]].concat();
+
+        // (empty IDAT, so that read_info() gets to the end of the header)
+        [
+            &b"\x89PNG\r\n\x1a\n\0\0\0\x0d"[..],
+            &ihdr,
+            &crc(&ihdr).to_be_bytes(),
+            b"\0\0\0\0IDAT",
+            &crc(b"IDAT").to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    // SOI + baseline SOF (1 component) + EOI
+    fn jpeg_header(width: u16, height: u16) -> Vec<u8> {
+        [
+            &[0xFF, 0xD8, 0xFF, 0xC0, 0, 11, 8][..],
+            &height.to_be_bytes(),
+            &width.to_be_bytes(),
+            &[1, 1, 0x11, 0, 0xFF, 0xD9],
+        ]
+        .concat()
+    }
+
+    // RIFF + VP8X (canvas size only) + empty VP8L
+    fn webp_header(width: u32, height: u32) -> Vec<u8> {
+        let canvas = [&[0; 4][..], &(width - 1).to_le_bytes()[..3], &(height - 1).to_le_bytes()[..3]].concat();
+
+        [&b"RIFF\x1e\0\0\0WEBPVP8X\x0a\0\0\0"[..], &canvas, b"VP8L\0\0\0\0"].concat()
+    }
+
+    fn encode_png(width: u32, height: u32, color: png::ColorType, depth: png::BitDepth, data: &[u8], palette: Option<&[u8]>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+
+        if let Some(palette) = palette {
+            encoder.set_palette(palette.to_vec());
+        }
+
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+
+        bytes
+    }
+}
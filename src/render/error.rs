@@ -0,0 +1,130 @@
+// errors
+//
+// x handles are just indices so only those out of range are detected
+//   (handle from another renderer might still be "valid" here)
+// x children are checked when inserted so the tree itself is always valid
+//   (including cycles, a container can't be inserted into its own subtree)
+// x backend errors are opaque (lost context, out of memory, ...)
+
+use super::backend::RenderBackend;
+use super::{BackgroundImage, Child, ContainerId, DecodingError, ImageId, TextId, UiState};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug)]
+pub enum RenderError {
+    InvalidContainer(ContainerId),
+    InvalidImage(ImageId),
+    InvalidText(TextId),
+    IndexOutOfRange { index: usize, len: usize },
+    // RGBA data doesn't match the size
+    InvalidImageData { width: i32, height: i32, len: usize },
+    RectOutOfImage,
+    // container would become its own descendant
+    Cycle(ContainerId),
+    Decoding(DecodingError),
+    Backend(String),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidContainer(c) => write!(f, "invalid container {:?}", c),
+            Self::InvalidImage(i) => write!(f, "invalid image {:?}", i),
+            Self::InvalidText(t) => write!(f, "invalid text {:?}", t),
+            Self::IndexOutOfRange { index, len } => write!(f, "index {} out of range (len is {})", index, len),
+            Self::InvalidImageData { width, height, len } => write!(f, "invalid image data ({} bytes for {}x{})", len, width, height),
+            Self::RectOutOfImage => write!(f, "rect out of image"),
+            Self::Cycle(c) => write!(f, "{:?} would be its own descendant", c),
+            Self::Decoding(e) => write!(f, "decoding: {}", e),
+            Self::Backend(e) => write!(f, "backend: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decoding(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodingError> for RenderError {
+    fn from(e: DecodingError) -> Self {
+        Self::Decoding(e)
+    }
+}
+
+// for backends
+pub fn check_image_data(width: i32, height: i32, data: &[u8]) -> Result<(), RenderError> {
+    if width < 0 || height < 0 || data.len() != width as usize * height as usize * 4 {
+        return Err(RenderError::InvalidImageData { width, height, len: data.len() });
+    }
+
+    Ok(())
+}
+
+impl<RB: RenderBackend, BK: Copy> UiState<RB, BK> {
+    pub(super) fn check_container(&self, container: ContainerId) -> Result<(), RenderError> {
+        if container.0 < self.bounds_keys.len() {
+            Ok(())
+        } else {
+            Err(RenderError::InvalidContainer(container))
+        }
+    }
+
+    pub(super) fn check_image(&self, image: ImageId) -> Result<(), RenderError> {
+        if image.0 < self.textures.len() {
+            Ok(())
+        } else {
+            Err(RenderError::InvalidImage(image))
+        }
+    }
+
+    pub(super) fn check_text(&self, text: TextId) -> Result<(), RenderError> {
+        if text.0 < self.text_layers.len() {
+            Ok(())
+        } else {
+            Err(RenderError::InvalidText(text))
+        }
+    }
+
+    pub(super) fn check_child(&self, child: Child) -> Result<(), RenderError> {
+        match child {
+            Child::Container(ch) => self.check_container(ch),
+            Child::Text(text) => self.check_text(text),
+        }
+    }
+
+    // `container` must not be anywhere in the `child` subtree
+    pub(super) fn check_cycle(&self, container: ContainerId, child: Child) -> Result<(), RenderError> {
+        let mut stack = match child {
+            Child::Container(ch) => vec![ch],
+            Child::Text(_) => return Ok(()),
+        };
+
+        while let Some(ct) = stack.pop() {
+            if ct == container {
+                return Err(RenderError::Cycle(container));
+            }
+
+            stack.extend(self.children[ct.0].iter().filter_map(|ch| match *ch {
+                Child::Container(ch) => Some(ch),
+                Child::Text(_) => None,
+            }));
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_background_images(&self, background_images: &[BackgroundImage]) -> Result<(), RenderError> {
+        for bg in background_images {
+            if let BackgroundImage::Image { image, .. } | BackgroundImage::NineSlice { image, .. } = *bg {
+                self.check_image(image)?;
+            }
+        }
+
+        Ok(())
+    }
+}
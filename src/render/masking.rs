@@ -0,0 +1,267 @@
+// clip-path & mask
+// - clip shapes are turned into paths (in absolute coords)
+// - gradient masks are generated into textures (so backend only needs textures)
+
+use super::{ClipPath, Color, PathCommand};
+use crate::commons::{Bounds, Pos};
+
+// mask textures are small, they are stretched (and filtered) anyway
+pub const MASK_SIZE: i32 = 64;
+
+// cubic bezier approximation of a quarter of a circle
+const KAPPA: f32 = 0.552_284_8;
+
+// line segments per curve (for hit testing)
+const CURVE_STEPS: usize = 8;
+
+pub fn clip_path(clip: &ClipPath, bounds: Bounds) -> Vec<PathCommand> {
+    let origin = bounds.a;
+    let p = |pos: Pos| pos.translate(origin);
+
+    match clip {
+        ClipPath::Inset { offsets, radius } => {
+            let inset = Bounds {
+                a: Pos {
+                    x: bounds.a.x + offsets.left,
+                    y: bounds.a.y + offsets.top,
+                },
+                b: Pos {
+                    x: bounds.b.x - offsets.right,
+                    y: bounds.b.y - offsets.bottom,
+                },
+            };
+
+            let radii = match radius {
+                Some(r) => super::border::fit_radii(inset, r),
+                None => [0.; 4],
+            };
+
+            rounded_rect(inset, radii)
+        }
+
+        ClipPath::Circle { center, radius } => ellipse(p(*center), *radius, *radius),
+        ClipPath::Ellipse { center, radius_x, radius_y } => ellipse(p(*center), *radius_x, *radius_y),
+
+        ClipPath::Polygon(points) => {
+            let mut path: Vec<_> = points.iter().map(|pt| PathCommand::LineTo(p(*pt))).collect();
+
+            if let Some(PathCommand::LineTo(first)) = path.first() {
+                path[0] = PathCommand::MoveTo(*first);
+                path.push(PathCommand::Close);
+            }
+
+            path
+        }
+
+        ClipPath::Path(commands) => commands
+            .iter()
+            .map(|c| match *c {
+                PathCommand::MoveTo(a) => PathCommand::MoveTo(p(a)),
+                PathCommand::LineTo(a) => PathCommand::LineTo(p(a)),
+                PathCommand::QuadTo(a, b) => PathCommand::QuadTo(p(a), p(b)),
+                PathCommand::CubicTo(a, b, c) => PathCommand::CubicTo(p(a), p(b), p(c)),
+                PathCommand::Close => PathCommand::Close,
+            })
+            .collect(),
+    }
+}
+
+fn ellipse(c: Pos, rx: f32, ry: f32) -> Vec<PathCommand> {
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    let p = |x, y| Pos { x: c.x + x, y: c.y + y };
+
+    vec![
+        PathCommand::MoveTo(p(rx, 0.)),
+        PathCommand::CubicTo(p(rx, ky), p(kx, ry), p(0., ry)),
+        PathCommand::CubicTo(p(-kx, ry), p(-rx, ky), p(-rx, 0.)),
+        PathCommand::CubicTo(p(-rx, -ky), p(-kx, -ry), p(0., -ry)),
+        PathCommand::CubicTo(p(kx, -ry), p(rx, -ky), p(rx, 0.)),
+        PathCommand::Close,
+    ]
+}
+
+// radii are top-left, top-right, bottom-right, bottom-left
+pub fn rounded_rect(Bounds { a, b }: Bounds, [tl, tr, br, bl]: [f32; 4]) -> Vec<PathCommand> {
+    let p = |x, y| Pos { x, y };
+    let k = 1. - KAPPA;
+
+    vec![
+        PathCommand::MoveTo(p(a.x + tl, a.y)),
+        PathCommand::LineTo(p(b.x - tr, a.y)),
+        PathCommand::CubicTo(p(b.x - tr * k, a.y), p(b.x, a.y + tr * k), p(b.x, a.y + tr)),
+        PathCommand::LineTo(p(b.x, b.y - br)),
+        PathCommand::CubicTo(p(b.x, b.y - br * k), p(b.x - br * k, b.y), p(b.x - br, b.y)),
+        PathCommand::LineTo(p(a.x + bl, b.y)),
+        PathCommand::CubicTo(p(a.x + bl * k, b.y), p(a.x, b.y - bl * k), p(a.x, b.y - bl)),
+        PathCommand::LineTo(p(a.x, a.y + tl)),
+        PathCommand::CubicTo(p(a.x, a.y + tl * k), p(a.x + tl * k, a.y), p(a.x + tl, a.y)),
+        PathCommand::Close,
+    ]
+}
+
+// rgba texture (MASK_SIZE x MASK_SIZE), only alpha matters
+pub fn gen_gradient_mask(stops: &[(f32, Color)], f: impl Fn(f32, f32) -> f32) -> Box<[u8]> {
+    let mut data = Vec::with_capacity((MASK_SIZE * MASK_SIZE * 4) as usize);
+
+    for y in 0..MASK_SIZE {
+        for x in 0..MASK_SIZE {
+            let (u, v) = ((x as f32 + 0.5) / MASK_SIZE as f32, (y as f32 + 0.5) / MASK_SIZE as f32);
+            data.extend(&[255, 255, 255, gradient_alpha(stops, f(u, v))]);
+        }
+    }
+
+    data.into_boxed_slice()
+}
+
+// nonzero winding rule, curves are flattened (subpaths are closed implicitly)
+pub fn contains(path: &[PathCommand], pos: Pos) -> bool {
+    let mut winding = 0;
+    let mut edge = |a: Pos, b: Pos| {
+        let side = (b.x - a.x) * (pos.y - a.y) - (pos.x - a.x) * (b.y - a.y);
+
+        if a.y <= pos.y && b.y > pos.y && side > 0. {
+            winding += 1;
+        } else if a.y > pos.y && b.y <= pos.y && side < 0. {
+            winding -= 1;
+        }
+    };
+
+    let (mut start, mut last) = (Pos::ZERO, Pos::ZERO);
+    let lerp = |a: Pos, b: Pos, t: f32| Pos {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+    };
+
+    for c in path {
+        match *c {
+            PathCommand::MoveTo(p) => {
+                edge(last, start);
+                start = p;
+                last = p;
+            }
+            PathCommand::LineTo(p) => {
+                edge(last, p);
+                last = p;
+            }
+            PathCommand::QuadTo(c, p) => {
+                let from = last;
+
+                for i in 1..=CURVE_STEPS {
+                    let t = i as f32 / CURVE_STEPS as f32;
+                    let next = lerp(lerp(from, c, t), lerp(c, p, t), t);
+                    edge(last, next);
+                    last = next;
+                }
+            }
+            PathCommand::CubicTo(c1, c2, p) => {
+                let from = last;
+
+                for i in 1..=CURVE_STEPS {
+                    let t = i as f32 / CURVE_STEPS as f32;
+                    let (a, b, c) = (lerp(from, c1, t), lerp(c1, c2, t), lerp(c2, p, t));
+                    let next = lerp(lerp(a, b, t), lerp(b, c, t), t);
+                    edge(last, next);
+                    last = next;
+                }
+            }
+            PathCommand::Close => {
+                edge(last, start);
+                last = start;
+            }
+        }
+    }
+
+    edge(last, start);
+
+    winding != 0
+}
+
+// including control points, so it's not the tightest one
+pub fn bounding_box(path: &[PathCommand]) -> Bounds {
+    let mut points = path.iter().flat_map(|c| match *c {
+        PathCommand::MoveTo(p) | PathCommand::LineTo(p) => vec![p],
+        PathCommand::QuadTo(c, p) => vec![c, p],
+        PathCommand::CubicTo(c1, c2, p) => vec![c1, c2, p],
+        PathCommand::Close => vec![],
+    });
+
+    let first = match points.next() {
+        Some(p) => p,
+        None => return Bounds::ZERO,
+    };
+
+    points.fold(Bounds { a: first, b: first }, |b, p| b.union(&Bounds { a: p, b: p }))
+}
+
+// position on the gradient line (0..1) for uv in the box
+pub fn linear(angle: f32) -> impl Fn(f32, f32) -> f32 {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let len = sin.abs() + cos.abs();
+
+    move |u, v| ((u - 0.5) * sin - (v - 0.5) * cos) / len + 0.5
+}
+
+pub fn radial(u: f32, v: f32) -> f32 {
+    ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() * 2.
+}
+
+fn gradient_alpha(stops: &[(f32, Color)], t: f32) -> u8 {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 0,
+    };
+
+    if t <= first.0 {
+        return first.1.a;
+    }
+
+    for w in stops.windows(2) {
+        let ((t0, c0), (t1, c1)) = (w[0], w[1]);
+
+        if t <= t1 {
+            let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1. };
+            return (c0.a as f32 + (c1.a as f32 - c0.a as f32) * f).round() as u8;
+        }
+    }
+
+    last.1.a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradients() {
+        let stops = [(0., Color::TRANSPARENT), (0.5, Color::BLACK), (1., Color::BLACK)];
+
+        assert_eq!(gradient_alpha(&stops, -1.), 0);
+        assert_eq!(gradient_alpha(&stops, 0.25), 128);
+        assert_eq!(gradient_alpha(&stops, 0.75), 255);
+
+        // to right
+        let f = linear(90.);
+        assert_eq!((f(0., 0.5), f(1., 0.5)), (0., 1.));
+
+        // to bottom
+        let f = linear(180.);
+        assert!((f(0.5, 0.) - 0.).abs() < 1e-6 && (f(0.5, 1.) - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn contains_point() {
+        let p = |x, y| Pos { x, y };
+        let circle = ellipse(p(10., 10.), 10., 10.);
+
+        assert!(contains(&circle, p(10., 10.)));
+        assert!(contains(&circle, p(3., 10.)));
+        // corner of the bounding box
+        assert!(!contains(&circle, p(1., 1.)));
+        assert!(!contains(&circle, p(25., 10.)));
+
+        // not closed explicitly
+        let triangle = [PathCommand::MoveTo(p(0., 0.)), PathCommand::LineTo(p(10., 0.)), PathCommand::LineTo(p(0., 10.))];
+        assert!(contains(&triangle, p(2., 2.)));
+        assert!(!contains(&triangle, p(8., 8.)));
+    }
+}
@@ -0,0 +1,222 @@
+// recorded layer ops
+//
+// x `RenderContext` doesn't talk to the backend directly
+//   so there's a chance to optimize what it has produced
+// x occlusion culling (optional)
+//   - walks the ops front-to-back, collecting opaque rects
+//   - rects/triangles which are fully covered by one of them are dropped
+//   - only within one layer (children contexts are opaque to us)
+//   - groups (filters, masks, ...) are left untouched, so are the ops behind
+//     snapshots (backdrop-filters, they can be blurred into the visible part)
+//     and behind children contexts (they might have a backdrop-filter somewhere inside)
+// x batching
+//   - consecutive rects are grouped by fill-style kind & texture
+//   - rect can only join an earlier batch if it doesn't overlap anything it'd jump over
+//   - adjacent same-color rects (sharing the whole edge) are merged
+
+use super::backend::{FillStyle, LayerBuilder, RenderBackend};
+use super::{BlendMode, Color, Filter, PathCommand};
+use crate::commons::{Bounds, Pos};
+
+pub enum Op<RB: RenderBackend> {
+    Rect(Bounds, FillStyle<RB>),
+    Triangle([Pos; 3], Color),
+    Layer(RB::LayerId, Pos),
+    PushFilters(Vec<Filter>),
+    PopFilters,
+    Snapshot(Bounds, RB::TextureId),
+    PushBlendMode(BlendMode),
+    PopBlendMode,
+    PushClipPath(Vec<PathCommand>),
+    PopClipPath,
+    PushMask(Bounds, RB::TextureId),
+    PopMask,
+}
+
+impl<RB: RenderBackend> LayerBuilder<RB> for Vec<Op<RB>> {
+    fn push_rect(&mut self, bounds: Bounds, style: FillStyle<RB>) {
+        self.push(Op::Rect(bounds, style));
+    }
+
+    fn push_triangle(&mut self, a: Pos, b: Pos, c: Pos, color: Color) {
+        self.push(Op::Triangle([a, b, c], color));
+    }
+
+    fn push_layer(&mut self, layer: RB::LayerId, origin: Pos) {
+        self.push(Op::Layer(layer, origin));
+    }
+
+    fn push_filters(&mut self, filters: &[Filter]) {
+        self.push(Op::PushFilters(filters.to_vec()));
+    }
+
+    fn pop_filters(&mut self) {
+        self.push(Op::PopFilters);
+    }
+
+    fn push_snapshot(&mut self, bounds: Bounds, texture: RB::TextureId) {
+        self.push(Op::Snapshot(bounds, texture));
+    }
+
+    fn push_blend_mode(&mut self, mode: BlendMode) {
+        self.push(Op::PushBlendMode(mode));
+    }
+
+    fn pop_blend_mode(&mut self) {
+        self.push(Op::PopBlendMode);
+    }
+
+    fn push_clip_path(&mut self, path: &[PathCommand]) {
+        self.push(Op::PushClipPath(path.to_vec()));
+    }
+
+    fn pop_clip_path(&mut self) {
+        self.push(Op::PopClipPath);
+    }
+
+    fn push_mask(&mut self, bounds: Bounds, texture: RB::TextureId) {
+        self.push(Op::PushMask(bounds, texture));
+    }
+
+    fn pop_mask(&mut self) {
+        self.push(Op::PopMask);
+    }
+}
+
+// send everything to the backend
+pub fn replay<RB: RenderBackend>(ops: Vec<Op<RB>>, builder: &mut RB::LayerBuilder) {
+    for op in ops {
+        match op {
+            Op::Rect(bounds, style) => builder.push_rect(bounds, style),
+            Op::Triangle([a, b, c], color) => builder.push_triangle(a, b, c, color),
+            Op::Layer(layer, origin) => builder.push_layer(layer, origin),
+            Op::PushFilters(filters) => builder.push_filters(&filters),
+            Op::PopFilters => builder.pop_filters(),
+            Op::Snapshot(bounds, texture) => builder.push_snapshot(bounds, texture),
+            Op::PushBlendMode(mode) => builder.push_blend_mode(mode),
+            Op::PopBlendMode => builder.pop_blend_mode(),
+            Op::PushClipPath(path) => builder.push_clip_path(&path),
+            Op::PopClipPath => builder.pop_clip_path(),
+            Op::PushMask(bounds, texture) => builder.push_mask(bounds, texture),
+            Op::PopMask => builder.pop_mask(),
+        }
+    }
+}
+
+// returns how many ops were dropped
+pub fn cull_occluded<RB: RenderBackend>(ops: &mut Vec<Op<RB>>) -> usize {
+    let mut occluders: Vec<Bounds> = Vec::new();
+    // (we go backwards, so pop comes first)
+    let mut depth = 0;
+    let mut keep = vec![true; ops.len()];
+
+    for (i, op) in ops.iter().enumerate().rev() {
+        match op {
+            Op::PopFilters | Op::PopBlendMode | Op::PopClipPath | Op::PopMask => depth += 1,
+            Op::PushFilters(_) | Op::PushBlendMode(_) | Op::PushClipPath(_) | Op::PushMask(..) => depth -= 1,
+            Op::Snapshot(..) | Op::Layer(..) => occluders.clear(),
+            _ if depth > 0 => {}
+
+            Op::Rect(bounds, style) => {
+                if is_covered(&occluders, bounds) {
+                    keep[i] = false;
+                } else if let FillStyle::SolidColor(Color { a: 255, .. }) = style {
+                    occluders.push(*bounds);
+                }
+            }
+
+            Op::Triangle([a, b, c], _) => {
+                let bounds = Bounds { a: *a, b: *a }.union(&Bounds { a: *b, b: *b }).union(&Bounds { a: *c, b: *c });
+                keep[i] = !is_covered(&occluders, &bounds);
+            }
+        }
+    }
+
+    let len = ops.len();
+    let mut keep = keep.into_iter();
+    ops.retain(|_| keep.next().unwrap());
+
+    len - ops.len()
+}
+
+fn is_covered(occluders: &[Bounds], Bounds { a, b }: &Bounds) -> bool {
+    occluders.iter().any(|o| o.a.x <= a.x && o.a.y <= a.y && o.b.x >= b.x && o.b.y >= b.y)
+}
+
+// returns (batches, merged rects)
+pub fn batch_rects<RB: RenderBackend>(ops: &mut Vec<Op<RB>>) -> (usize, usize) {
+    let mut res = Vec::with_capacity(ops.len());
+    let mut batches: Vec<Vec<(Bounds, FillStyle<RB>)>> = Vec::new();
+    let (mut count, mut merged) = (0, 0);
+
+    for op in ops.drain(..) {
+        match op {
+            Op::Rect(bounds, style) => merged += add_to_batch(&mut batches, bounds, style) as usize,
+            _ => {
+                count += batches.len();
+                res.extend(batches.drain(..).flatten().map(|(bounds, style)| Op::Rect(bounds, style)));
+                res.push(op);
+            }
+        }
+    }
+
+    count += batches.len();
+    res.extend(batches.drain(..).flatten().map(|(bounds, style)| Op::Rect(bounds, style)));
+    *ops = res;
+
+    (count, merged)
+}
+
+// returns true if it was merged with the last rect of the batch
+fn add_to_batch<RB: RenderBackend>(batches: &mut Vec<Vec<(Bounds, FillStyle<RB>)>>, bounds: Bounds, style: FillStyle<RB>) -> bool {
+    // latest batch we can go to
+    let mut target = None;
+
+    for (i, batch) in batches.iter().enumerate().rev() {
+        if same_batch(&batch[0].1, &style) {
+            target = Some(i);
+            break;
+        }
+
+        if batch.iter().any(|(b, _)| b.intersects(&bounds)) {
+            break;
+        }
+    }
+
+    let batch = match target {
+        Some(i) => &mut batches[i],
+        None => {
+            batches.push(vec![(bounds, style)]);
+            return false;
+        }
+    };
+
+    let (last, last_style) = batch.last_mut().unwrap();
+
+    if let (FillStyle::SolidColor(a), FillStyle::SolidColor(b)) = (&*last_style, &style) {
+        if a == b && is_adjacent(last, &bounds) {
+            *last = last.union(&bounds);
+            return true;
+        }
+    }
+
+    batch.push((bounds, style));
+    false
+}
+
+fn same_batch<RB: RenderBackend>(a: &FillStyle<RB>, b: &FillStyle<RB>) -> bool {
+    match (a, b) {
+        (FillStyle::SolidColor(_), FillStyle::SolidColor(_)) => true,
+        (FillStyle::Texture(a, ..), FillStyle::Texture(b, ..)) => a == b,
+        (FillStyle::Msdf { texture: a, .. }, FillStyle::Msdf { texture: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+// sharing the whole edge, so the union is still a rect
+fn is_adjacent(a: &Bounds, b: &Bounds) -> bool {
+    let same_rows = a.a.y == b.a.y && a.b.y == b.b.y;
+    let same_cols = a.a.x == b.a.x && a.b.x == b.b.x;
+
+    (same_rows && (a.b.x == b.a.x || b.b.x == a.a.x)) || (same_cols && (a.b.y == b.a.y || b.b.y == a.a.y))
+}
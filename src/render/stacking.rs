@@ -0,0 +1,108 @@
+// stacking contexts (paint order & hit testing)
+//
+// x root is always a stacking context
+// x z-index, opacity, filters, backdrop-filters, blend-mode, clip-path & mask create new ones
+//   - transform too (TODO, once it's implemented)
+// x paint order of one context:
+//   - the root itself (outline, backgrounds, shadows)
+//   - contexts with negative z-index
+//   - the rest of the subtree in tree order (and borders)
+//   - contexts with zero/auto z-index in tree order, then positive z-index
+// x contexts are hoisted from anywhere in the subtree (until another context)
+// x hit testing goes in exactly the reverse order
+//   - clip-path hides the subtree outside of it
+//   - overflow doesn't (same as for painting, TODO once it's clipped)
+
+use super::{masking, BlendMode, Child, ContainerId, UiState};
+use crate::commons::{Bounds, Pos};
+use std::ops::Index;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackedChild {
+    pub z_index: i32,
+    pub container: ContainerId,
+    // absolute
+    pub bounds: Bounds,
+}
+
+impl<RB: super::backend::RenderBackend, BK: Copy> UiState<RB, BK> {
+    pub(super) fn creates_stacking_context(&self, container: ContainerId) -> bool {
+        let i = container.0;
+
+        self.z_indices[i].is_some()
+            || self.opacities[i] < 1.
+            || !self.filters[i].is_empty()
+            || !self.backdrop_filters[i].is_empty()
+            || self.blend_modes[i] != BlendMode::Normal
+            || self.clip_paths[i].is_some()
+            || self.masks[i].is_some()
+    }
+
+    // descendants which are painted as part of this context (sorted by z-index)
+    // split into (negative, rest)
+    pub(super) fn stacked_children(
+        &self,
+        container: ContainerId,
+        bounds: Bounds,
+        bounds_map: &impl Index<BK, Output = Bounds>,
+    ) -> (Vec<StackedChild>, Vec<StackedChild>) {
+        let mut res = Vec::new();
+        self.collect_stacked_children(container, bounds, bounds_map, &mut res);
+
+        // stable, so tree order is kept for the same z-index
+        res.sort_by_key(|ch| ch.z_index);
+
+        let rest = res.split_off(res.partition_point(|ch| ch.z_index < 0));
+
+        (res, rest)
+    }
+
+    fn collect_stacked_children(&self, container: ContainerId, bounds: Bounds, bounds_map: &impl Index<BK, Output = Bounds>, res: &mut Vec<StackedChild>) {
+        for ch in &self.children[container.0] {
+            if let Child::Container(ch) = *ch {
+                let ch_bounds = bounds_map[self.bounds_keys[ch.0]].translate(bounds.a);
+
+                if self.creates_stacking_context(ch) {
+                    res.push(StackedChild {
+                        z_index: self.z_indices[ch.0].unwrap_or(0),
+                        container: ch,
+                        bounds: ch_bounds,
+                    });
+                } else {
+                    self.collect_stacked_children(ch, ch_bounds, bounds_map, res);
+                }
+            }
+        }
+    }
+
+    // topmost container at the pos, `bounds` are for the `container`
+    pub(super) fn hit_test(&self, container: ContainerId, bounds: Bounds, bounds_map: &impl Index<BK, Output = Bounds>, pos: Pos) -> Option<ContainerId> {
+        if let Some(clip) = &self.clip_paths[container.0] {
+            if !masking::contains(&masking::clip_path(clip, bounds), pos) {
+                return None;
+            }
+        }
+
+        let (negative, rest) = self.stacked_children(container, bounds, bounds_map);
+
+        rest.iter()
+            .rev()
+            .find_map(|ch| self.hit_test(ch.container, ch.bounds, bounds_map, pos))
+            .or_else(|| self.hit_test_flow(container, bounds, bounds_map, pos))
+            .or_else(|| negative.iter().rev().find_map(|ch| self.hit_test(ch.container, ch.bounds, bounds_map, pos)))
+            .or_else(|| if bounds.contains(pos) { Some(container) } else { None })
+    }
+
+    // non-context descendants, topmost first
+    fn hit_test_flow(&self, container: ContainerId, bounds: Bounds, bounds_map: &impl Index<BK, Output = Bounds>, pos: Pos) -> Option<ContainerId> {
+        self.children[container.0].iter().rev().find_map(|ch| match *ch {
+            Child::Container(ch) if !self.creates_stacking_context(ch) => {
+                let ch_bounds = bounds_map[self.bounds_keys[ch.0]].translate(bounds.a);
+
+                self.hit_test_flow(ch, ch_bounds, bounds_map, pos)
+                    .or_else(|| if ch_bounds.contains(pos) { Some(ch) } else { None })
+            }
+            _ => None,
+        })
+    }
+}
@@ -22,7 +22,7 @@ pub struct BorderRadius {
     pub bottom_left: f32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Overflow {
     Visible,
     Hidden,
@@ -40,6 +40,8 @@ pub struct OutlineShadow {
 #[derive(Debug, Clone, Copy)]
 pub struct Outline {
     pub width: f32,
+    // gap between the outline and the border box (can be negative)
+    pub offset: f32,
     pub style: OutlineStyle,
     pub color: Color,
 }
@@ -47,13 +49,16 @@ pub struct Outline {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OutlineStyle {
     Solid,
+    Dashed,
+    Dotted,
+    Double,
 }
 
 /// Packed color
 /// note that u32 could improve interop or CPU but GPU is float-only
 /// and bitwise ops are slow so it still needs to be unpacked during
 /// `VertexAttribPointer()` as it is done now
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -85,11 +90,127 @@ impl Debug for Color {
 
 #[derive(Debug, Clone, Copy)]
 pub enum BackgroundImage {
-    Image { image: ImageId },
+    Image {
+        image: ImageId,
+        size: BackgroundSize,
+        position: BackgroundPosition,
+        // (x, y)
+        repeat: (BackgroundRepeat, BackgroundRepeat),
+        // what the position is relative to
+        origin: BackgroundBox,
+        // where it is painted
+        clip: BackgroundBox,
+    },
+    // border-image
+    NineSlice {
+        image: ImageId,
+        // insets into the image (in image pixels)
+        slice: SideOffsets,
+        // where the slices go (insets into the container)
+        widths: SideOffsets,
+        // (top/bottom/center, left/right/center)
+        repeat: (NineSliceRepeat, NineSliceRepeat),
+        // draw the center too
+        fill: bool,
+    },
     LinearGradient {},
     RadialGradient {},
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundSize {
+    Auto,
+    Contain,
+    Cover,
+    // None means auto (keep aspect ratio)
+    Explicit(Option<f32>, Option<f32>),
+    // stretch to the whole origin box (`100% 100%`)
+    Fill,
+}
+
+// `align * (origin box - image size) + offset`
+// so `align` 0.5 is center, 1 is right/bottom, etc.
+#[derive(Debug, Clone, Copy)]
+pub struct BackgroundPosition {
+    pub align_x: f32,
+    pub align_y: f32,
+    pub offset: Pos,
+}
+
+impl BackgroundPosition {
+    pub const TOP_LEFT: Self = Self {
+        align_x: 0.,
+        align_y: 0.,
+        offset: Pos::ZERO,
+    };
+    pub const CENTER: Self = Self {
+        align_x: 0.5,
+        align_y: 0.5,
+        offset: Pos::ZERO,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundRepeat {
+    Repeat,
+    NoRepeat,
+    // as many as fit, with space between
+    Space,
+    // scaled to fit whole number of times
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NineSliceRepeat {
+    Stretch,
+    // centered, partial tiles at both ends
+    Repeat,
+    // scaled to fit whole number of times
+    Round,
+    // whole tiles, remaining space is distributed around them
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SideOffsets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl SideOffsets {
+    pub const ZERO: Self = Self::uniform(0.);
+
+    pub const fn uniform(v: f32) -> Self {
+        Self {
+            top: v,
+            right: v,
+            bottom: v,
+            left: v,
+        }
+    }
+}
+
+// content-box would need padding which is not known here
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundBox {
+    BorderBox,
+    // inside of the border
+    PaddingBox,
+}
+
+// how the image should be sampled when it's scaled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageRendering {
+    // bilinear (default)
+    Smooth,
+    // nearest, for pixel-art
+    Pixelated,
+    // prefiltered when downscaling (slower)
+    HighQuality,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Border {
     pub top: Option<BorderSide>,
@@ -109,6 +230,13 @@ pub struct BorderSide {
 pub enum BorderStyle {
     None,
     Solid,
+    Dashed,
+    Dotted,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -118,3 +246,87 @@ pub struct InsetShadow {
     pub spread: f32,
     pub color: Color,
 }
+
+// applied in order, to the whole container (including children)
+// amounts are fractions (1. = 100%), angles are in degrees
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    // standard deviation
+    Blur(f32),
+    Brightness(f32),
+    Contrast(f32),
+    Grayscale(f32),
+    HueRotate(f32),
+    Invert(f32),
+    Opacity(f32),
+    Saturate(f32),
+    Sepia(f32),
+    DropShadow { offset: Pos, blur: f32, color: Color },
+}
+
+// how the container is composited with what's behind it (mix-blend-mode)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+// clips the whole container (including children)
+// positions are relative to the top-left corner of the border box
+#[derive(Debug, Clone)]
+pub enum ClipPath {
+    Inset { offsets: SideOffsets, radius: Option<BorderRadius> },
+    Circle { center: Pos, radius: f32 },
+    Ellipse { center: Pos, radius_x: f32, radius_y: f32 },
+    Polygon(Vec<Pos>),
+    Path(Vec<PathCommand>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PathCommand {
+    MoveTo(Pos),
+    LineTo(Pos),
+    QuadTo(Pos, Pos),
+    CubicTo(Pos, Pos, Pos),
+    Close,
+}
+
+// alpha of the mask is multiplied with the container (including children)
+// it's always stretched over the border box
+#[derive(Debug, Clone)]
+pub enum Mask {
+    Image(ImageId),
+    // degrees (0 = to top, 90 = to right), stops are (offset, color)
+    LinearGradient { angle: f32, stops: Vec<(f32, Color)> },
+    // from the center to the sides
+    RadialGradient { stops: Vec<(f32, Color)> },
+}
+
+// what happened during the last `render_container()`
+// (only rebuilt layers are counted)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub rendered: usize,
+    // skipped because they were outside of the viewport (with their subtrees)
+    pub culled: usize,
+    // ops hidden behind opaque rects (if enabled)
+    pub occluded: usize,
+    // runs of rects with the same fill-style kind & texture
+    pub batches: usize,
+    // adjacent same-color rects merged into one
+    pub merged: usize,
+}
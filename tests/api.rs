@@ -0,0 +1,229 @@
+// only the public API is used here
+
+use new_renderer::*;
+
+#[test]
+fn render_tree() {
+    let mut r = Renderer::new(LogBackend::default());
+    let bounds = vec![rect(0., 0., 100., 100.), rect(10., 10., 20., 20.), rect(50., 50., 60., 60.)];
+
+    let parent = r.create_container(0);
+    let child1 = r.create_container(1);
+    let child2 = r.create_container(2);
+
+    r.insert_child(parent, 0, Child::Container(child1)).unwrap();
+    r.insert_child(parent, 1, Child::Container(child2)).unwrap();
+    r.set_background_color(child1, Color::RED).unwrap();
+    r.set_background_color(child2, Color::BLUE).unwrap();
+    r.render_container(parent, &bounds).unwrap();
+
+    assert_eq!(
+        r.backend().log,
+        vec![
+            "create_layer 0",
+            "rebuild_layer 0",
+            "rect Bounds((10.0, 10.0), (20.0, 20.0)) SolidColor(#ff0000)",
+            "rect Bounds((50.0, 50.0), (60.0, 60.0)) SolidColor(#0000ff)",
+            "render_layer 0 1",
+        ]
+    );
+    assert_eq!(r.stats().rendered, 3);
+    assert_eq!(r.damage(), &[bounds[0]]);
+
+    // nothing has changed
+    r.render_container(parent, &bounds).unwrap();
+    assert_eq!(r.damage(), &[]);
+
+    assert_eq!(r.hit_test(parent, &bounds, Pos { x: 15., y: 15. }).unwrap(), Some(child1));
+    assert_eq!(r.hit_test(parent, &bounds, Pos { x: 30., y: 30. }).unwrap(), Some(parent));
+    assert_eq!(r.hit_test(parent, &bounds, Pos { x: 200., y: 0. }).unwrap(), None);
+}
+
+#[test]
+fn images() {
+    let mut r = Renderer::new(LogBackend::default());
+    let c = r.create_container(0);
+    let img = r.create_image(1, 1, Box::new([255, 0, 0, 255])).unwrap();
+
+    r.set_background_images(
+        c,
+        vec![BackgroundImage::Image {
+            image: img,
+            size: BackgroundSize::Fill,
+            position: BackgroundPosition::TOP_LEFT,
+            repeat: (BackgroundRepeat::NoRepeat, BackgroundRepeat::NoRepeat),
+            origin: BackgroundBox::BorderBox,
+            clip: BackgroundBox::BorderBox,
+        }],
+    )
+    .unwrap();
+    r.render_container(c, &vec![rect(0., 0., 10., 10.)]).unwrap();
+
+    assert!(r.backend().log.contains(&"create_texture 1x1".to_string()));
+    assert!(r.backend().log.contains(&"rect Bounds((0.0, 0.0), (10.0, 10.0)) Texture".to_string()));
+}
+
+#[test]
+fn errors() {
+    let mut r = Renderer::<_, usize>::new(LogBackend::default());
+    let c = r.create_container(0);
+
+    let mut other = Renderer::<_, usize>::new(LogBackend::default());
+    other.create_container(0);
+    let foreign = other.create_container(0);
+
+    assert!(matches!(r.set_color(foreign, Color::RED), Err(RenderError::InvalidContainer(_))));
+    assert!(matches!(r.insert_child(c, 0, Child::Container(c)), Err(RenderError::Cycle(_))));
+
+    let text = r.create_text(1);
+    assert!(matches!(
+        r.insert_child(c, 1, Child::Text(text)),
+        Err(RenderError::IndexOutOfRange { index: 1, len: 0 })
+    ));
+    assert!(matches!(r.create_image(2, 2, Box::new([0; 4])), Err(RenderError::InvalidImageData { .. })));
+    assert!(matches!(
+        r.create_image_from_encoded(b"nope"),
+        Err(RenderError::Decoding(DecodingError::UnknownFormat))
+    ));
+
+    // can be boxed & printed
+    let e: Box<dyn std::error::Error> = r.set_opacity(foreign, 0.5).unwrap_err().into();
+    assert_eq!(e.to_string(), "invalid container ContainerId(1)");
+}
+
+#[cfg(feature = "raqote")]
+#[test]
+fn raqote_pixels() {
+    let mut backend = RaqoteBackend::new(4, 1);
+    backend.set_clear_color(Color::WHITE);
+
+    let mut r = Renderer::new(backend);
+    let c = r.create_container(0);
+    let ch = r.create_container(1);
+
+    r.insert_child(c, 0, Child::Container(ch)).unwrap();
+    r.set_background_color(ch, Color::BLUE).unwrap();
+    r.render_container(c, &vec![rect(0., 0., 4., 1.), rect(2., 0., 4., 1.)]).unwrap();
+
+    assert_eq!(r.backend().pixels(), &[255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 255, 255, 0, 0, 255, 255]);
+}
+
+fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Bounds {
+    Bounds {
+        a: Pos { x: x0, y: y0 },
+        b: Pos { x: x1, y: y1 },
+    }
+}
+
+// backend implemented outside of the crate
+#[derive(Debug, Default)]
+struct LogBackend {
+    log: Vec<String>,
+    layers: usize,
+    textures: usize,
+}
+
+impl RenderBackend for LogBackend {
+    type LayerId = usize;
+    type TextureId = usize;
+    type LayerBuilder = Vec<String>;
+
+    fn create_layer(&mut self) -> usize {
+        self.log.push(format!("create_layer {}", self.layers));
+        self.layers += 1;
+
+        self.layers - 1
+    }
+
+    fn rebuild_layer_with(&mut self, layer: usize, mut f: impl FnMut(&mut Vec<String>)) {
+        self.log.push(format!("rebuild_layer {}", layer));
+        f(&mut self.log);
+    }
+
+    fn render_layer(&mut self, layer: usize, damage: &[Bounds]) -> Result<(), RenderError> {
+        self.log.push(format!("render_layer {} {}", layer, damage.len()));
+
+        Ok(())
+    }
+
+    fn create_texture(&mut self, width: i32, height: i32, data: Box<[u8]>) -> Result<usize, RenderError> {
+        if data.len() != (width * height * 4) as usize {
+            return Err(RenderError::InvalidImageData { width, height, len: data.len() });
+        }
+
+        self.log.push(format!("create_texture {}x{}", width, height));
+        self.textures += 1;
+
+        Ok(self.textures - 1)
+    }
+
+    fn update_texture(&mut self, texture: usize, _f: impl FnMut(&mut [u8])) {
+        self.log.push(format!("update_texture {}", texture));
+    }
+
+    fn create_snapshot_texture(&mut self) -> Result<usize, RenderError> {
+        self.log.push("create_snapshot_texture".to_string());
+        self.textures += 1;
+
+        Ok(self.textures - 1)
+    }
+
+    fn delete_texture(&mut self, texture: usize) {
+        self.log.push(format!("delete_texture {}", texture));
+    }
+}
+
+impl LayerBuilder<LogBackend> for Vec<String> {
+    fn push_rect(&mut self, bounds: Bounds, style: FillStyle<LogBackend>) {
+        // kind only (textures & uvs are impl details)
+        match style {
+            FillStyle::SolidColor(color) => self.push(format!("rect {:?} SolidColor({:?})", bounds, color)),
+            FillStyle::Texture(..) => self.push(format!("rect {:?} Texture", bounds)),
+            FillStyle::Msdf { .. } => self.push(format!("rect {:?} Msdf", bounds)),
+        }
+    }
+
+    fn push_triangle(&mut self, a: Pos, b: Pos, c: Pos, color: Color) {
+        self.push(format!("triangle {:?} {:?} {:?} {:?}", a, b, c, color));
+    }
+
+    fn push_layer(&mut self, layer: usize, origin: Pos) {
+        self.push(format!("layer {} {:?}", layer, origin));
+    }
+
+    fn push_filters(&mut self, filters: &[Filter]) {
+        self.push(format!("push_filters {:?}", filters));
+    }
+
+    fn pop_filters(&mut self) {
+        self.push("pop_filters".to_string());
+    }
+
+    fn push_snapshot(&mut self, bounds: Bounds, texture: usize) {
+        self.push(format!("snapshot {:?} {}", bounds, texture));
+    }
+
+    fn push_blend_mode(&mut self, mode: BlendMode) {
+        self.push(format!("push_blend_mode {:?}", mode));
+    }
+
+    fn pop_blend_mode(&mut self) {
+        self.push("pop_blend_mode".to_string());
+    }
+
+    fn push_clip_path(&mut self, path: &[PathCommand]) {
+        self.push(format!("push_clip_path {:?}", path));
+    }
+
+    fn pop_clip_path(&mut self) {
+        self.push("pop_clip_path".to_string());
+    }
+
+    fn push_mask(&mut self, bounds: Bounds, texture: usize) {
+        self.push(format!("push_mask {:?} {}", bounds, texture));
+    }
+
+    fn pop_mask(&mut self) {
+        self.push("pop_mask".to_string());
+    }
+}